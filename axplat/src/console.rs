@@ -1,6 +1,41 @@
 //! Console input and output.
 
 use core::fmt::{Arguments, Result, Write};
+use core::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize, Ordering};
+
+#[cfg(feature = "klog")]
+pub mod klog;
+#[cfg(feature = "log")]
+mod logger;
+
+#[cfg(feature = "log")]
+pub use self::logger::init_logger;
+
+/// The function that returns the current CPU ID, or null if not set.
+static CPU_ID_FN: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Sets the function used to get the current CPU ID.
+///
+/// The CPU ID is printed by the console logger, and is used by the kernel log
+/// buffer to detect re-entrant writes. Platforms that can read the CPU ID from
+/// the hardware call it in [`init_early`](crate::init::init_early). Otherwise,
+/// the kernel should call it once its per-CPU data is initialized.
+pub fn set_cpu_id_fn(f: fn() -> usize) {
+    CPU_ID_FN.store(f as *mut (), Ordering::Release);
+}
+
+/// Returns the current CPU ID, or `None` before [`set_cpu_id_fn`] is called.
+#[cfg(any(feature = "log", feature = "klog"))]
+fn current_cpu_id() -> Option<usize> {
+    let f = CPU_ID_FN.load(Ordering::Acquire);
+    if f.is_null() {
+        None
+    } else {
+        // SAFETY: only `fn() -> usize` pointers are stored in `CPU_ID_FN`.
+        let f = unsafe { core::mem::transmute::<*mut (), fn() -> usize>(f) };
+        Some(f())
+    }
+}
 
 /// Console input and output interface.
 #[def_plat_interface]
@@ -12,6 +47,196 @@ pub trait ConsoleIf {
     ///
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize;
+
+    /// Returns the IRQ number of the console input, or `None` if the console
+    /// input is not interrupt-driven.
+    ///
+    /// When the IRQ is triggered, the received bytes are buffered by the
+    /// platform and can be read by [`read_bytes`]. It is used by kernels to
+    /// wake up the readers waiting for console input.
+    fn irq_num() -> Option<usize>;
+}
+
+/// A lock-free ring buffer for bytes received by the console.
+///
+/// It is filled by the console IRQ handler with [`push`](Self::push), and
+/// drained by [`ConsoleIf::read_bytes`] with [`pop`](Self::pop) or
+/// [`read`](Self::read). Only one producer is allowed at a time, while
+/// consumers can be concurrent.
+pub struct RxRingBuffer<const N: usize> {
+    buf: [AtomicU8; N],
+    /// Index of the next byte to be read.
+    head: AtomicUsize,
+    /// Index of the next byte to be written.
+    tail: AtomicUsize,
+}
+
+impl<const N: usize> RxRingBuffer<N> {
+    /// Creates a new empty ring buffer.
+    pub const fn new() -> Self {
+        assert!(N > 0, "the capacity of the ring buffer must not be zero");
+        Self {
+            buf: [const { AtomicU8::new(0) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of bytes in the buffer.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        self.tail.load(Ordering::Acquire).wrapping_sub(head)
+    }
+
+    /// Whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends a byte to the buffer.
+    ///
+    /// Returns `false` if the buffer is full, in which case the byte is
+    /// dropped.
+    pub fn push(&self, byte: u8) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) >= N {
+            return false;
+        }
+        self.buf[tail % N].store(byte, Ordering::Relaxed);
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Removes the oldest byte from the buffer, or returns `None` if it is
+    /// empty.
+    pub fn pop(&self) -> Option<u8> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            if head == self.tail.load(Ordering::Acquire) {
+                return None;
+            }
+            let byte = self.buf[head % N].load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                head.wrapping_add(1),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(byte),
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Moves bytes from the buffer into the given mutable slice.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&self, bytes: &mut [u8]) -> usize {
+        let mut read_len = 0;
+        while read_len < bytes.len() {
+            match self.pop() {
+                Some(c) => bytes[read_len] = c,
+                None => break,
+            }
+            read_len += 1;
+        }
+        read_len
+    }
+}
+
+impl<const N: usize> Default for RxRingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The receive side of an interrupt-driven console, shared by console drivers.
+///
+/// It keeps the IRQ number of the console input once the IRQ handler of the
+/// driver is registered by [`register_irq`](Self::register_irq), and buffers
+/// the bytes received by the handler in an [`RxRingBuffer`]. Drivers only
+/// provide the device access: a closure that takes a received byte from the
+/// device, if any.
+///
+/// The driver should hold its device lock in the IRQ handler and in
+/// [`ConsoleIf::read_bytes`], so that buffered bytes are not reordered with
+/// the ones still in the device.
+pub struct ConsoleRx<const N: usize> {
+    buf: RxRingBuffer<N>,
+    /// The IRQ number, or `usize::MAX` if the receive interrupt is not
+    /// enabled.
+    irq_num: AtomicUsize,
+}
+
+impl<const N: usize> ConsoleRx<N> {
+    /// Creates a new console receiver, whose buffer can hold `N` bytes.
+    pub const fn new() -> Self {
+        Self {
+            buf: RxRingBuffer::new(),
+            irq_num: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    /// Registers `handler` for the given IRQ by [`irq::register`], and records
+    /// the IRQ number if it succeeds.
+    ///
+    /// Returns `false` if the registration failed, in which case the console
+    /// input remains polled.
+    ///
+    /// [`irq::register`]: crate::irq::register
+    pub fn register_irq(&self, irq_num: usize, handler: crate::irq::IrqHandler) -> bool {
+        if crate::irq::register(irq_num, handler) {
+            self.irq_num.store(irq_num, Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the IRQ number of the console input, or `None` if the receive
+    /// interrupt is not enabled.
+    ///
+    /// It is intended as the implementation of [`ConsoleIf::irq_num`].
+    pub fn irq_num(&self) -> Option<usize> {
+        match self.irq_num.load(Ordering::Acquire) {
+            usize::MAX => None,
+            irq_num => Some(irq_num),
+        }
+    }
+
+    /// Moves all bytes received by the device into the buffer. Bytes are
+    /// dropped if the buffer is full.
+    ///
+    /// It is called by the IRQ handler, where `getchar` takes a byte from the
+    /// device until none is left.
+    pub fn receive(&self, mut getchar: impl FnMut() -> Option<u8>) {
+        while let Some(c) = getchar() {
+            self.buf.push(c);
+        }
+    }
+
+    /// Reads bytes into the given mutable slice, first from the buffer, then
+    /// from the device by `getchar`.
+    ///
+    /// Returns the number of bytes read. It is intended as the implementation
+    /// of [`ConsoleIf::read_bytes`].
+    pub fn read(&self, bytes: &mut [u8], mut getchar: impl FnMut() -> Option<u8>) -> usize {
+        let mut read_len = self.buf.read(bytes);
+        while read_len < bytes.len() {
+            match getchar() {
+                Some(c) => bytes[read_len] = c,
+                None => break,
+            }
+            read_len += 1;
+        }
+        read_len
+    }
+}
+
+impl<const N: usize> Default for ConsoleRx<N> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 struct EarlyConsole;
@@ -19,6 +244,8 @@ struct EarlyConsole;
 impl Write for EarlyConsole {
     fn write_str(&mut self, s: &str) -> Result {
         write_bytes(s.as_bytes());
+        #[cfg(feature = "klog")]
+        klog::write(s.as_bytes());
         Ok(())
     }
 }
@@ -34,7 +261,7 @@ macro_rules! console_print {
 /// Simple console print operation, with a newline.
 #[macro_export]
 macro_rules! console_println {
-    () => { $crate::ax_print!("\n") };
+    () => { $crate::console_print!("\n") };
     ($($arg:tt)*) => {
         $crate::console::__simple_print(format_args!("{}\n", format_args!($($arg)*)));
     }
@@ -44,3 +271,69 @@ macro_rules! console_println {
 pub fn __simple_print(fmt: Arguments) {
     EarlyConsole.write_fmt(fmt).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ConsoleRx, RxRingBuffer};
+
+    #[test]
+    fn rx_ring_buffer() {
+        let rb = RxRingBuffer::<4>::new();
+        assert!(rb.is_empty());
+        assert_eq!(rb.pop(), None);
+
+        for c in b"abcd" {
+            assert!(rb.push(*c));
+        }
+        assert!(!rb.push(b'e')); // full
+        assert_eq!(rb.len(), 4);
+        assert_eq!(rb.pop(), Some(b'a'));
+        assert!(rb.push(b'f')); // wrap around
+
+        let mut buf = [0; 8];
+        assert_eq!(rb.read(&mut buf), 4);
+        assert_eq!(&buf[..4], b"bcdf");
+        assert!(rb.is_empty());
+    }
+
+    #[test]
+    fn rx_ring_buffer_concurrent() {
+        const COUNT: usize = 10000;
+        let rb = RxRingBuffer::<64>::new();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..COUNT {
+                    while !rb.push(i as u8) {
+                        std::hint::spin_loop();
+                    }
+                }
+            });
+            let mut buf = [0; 7];
+            let mut next = 0;
+            while next < COUNT {
+                let n = rb.read(&mut buf);
+                for &c in &buf[..n] {
+                    assert_eq!(c, next as u8);
+                    next += 1;
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn console_rx() {
+        let rx = ConsoleRx::<4>::new();
+        assert_eq!(rx.irq_num(), None);
+
+        let mut device = b"abcdef".iter().copied();
+        rx.receive(|| device.next());
+        assert!(device.next().is_none()); // drained, with 'e' and 'f' dropped
+
+        let mut device = b"xyz".iter().copied();
+        let mut buf = [0; 6];
+        assert_eq!(rx.read(&mut buf, || device.next()), 6);
+        assert_eq!(&buf, b"abcdxy");
+        assert_eq!(rx.read(&mut buf, || device.next()), 1);
+        assert_eq!(buf[0], b'z');
+    }
+}
@@ -0,0 +1,402 @@
+//! In-memory kernel log ring buffer.
+//!
+//! All output printed by [`console_print!`](crate::console_print) and the
+//! console logger is also captured in a fixed-size ring buffer, with each line
+//! prefixed by the [`monotonic_time`](crate::time::monotonic_time) timestamp.
+//! The captured output can be read back by [`read`] (e.g., for a `dmesg`
+//! command) or dumped to the console by [`dump`] (e.g., after a crash).
+//!
+//! The buffer is in the kernel image by default. It can be moved to a reserved
+//! physical memory range by [`set_buffer`], so that its contents survive a warm
+//! reset. Platforms with the `klog` feature enabled move it to the range given
+//! by `klog-paddr` and `klog-size` in their configuration, which is reported
+//! in [`reserved_phys_ram_ranges`](crate::mem::reserved_phys_ram_ranges).
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+
+use crate::mem::{RawRange, ranges_contain};
+
+/// The size of the default ring buffer in the kernel image, including the
+/// header.
+pub const DEFAULT_KLOG_SIZE: usize = 16 * 1024;
+
+/// Identifies a valid ring buffer header ("AXKLOG\0\0").
+const KLOG_MAGIC: u64 = u64::from_le_bytes(*b"AXKLOG\0\0");
+
+/// Separates the output of a previous boot from the output of the current one.
+const BOOT_SEPARATOR: &[u8] = b"---- new boot ----\n";
+
+/// The header at the beginning of a ring buffer, followed by the data area.
+#[repr(C)]
+struct Header {
+    magic: u64,
+    /// The size of the data area.
+    capacity: u64,
+    /// The total number of bytes ever written.
+    head: u64,
+    /// Whether the next byte starts a new line.
+    line_start: u64,
+}
+
+const HEADER_SIZE: usize = core::mem::size_of::<Header>();
+
+/// A ring buffer over a raw memory area.
+struct Ring {
+    hdr: *mut Header,
+}
+
+impl Ring {
+    /// Initializes a ring buffer at the given memory area. The existing
+    /// contents are kept if the area already holds a valid ring buffer of the
+    /// same size.
+    ///
+    /// Returns the ring buffer and whether the existing contents are kept.
+    ///
+    /// # Safety
+    ///
+    /// The memory area must be valid for reads and writes, 8-byte aligned, and
+    /// larger than the header.
+    unsafe fn init(base: *mut u8, size: usize) -> (Self, bool) {
+        let hdr = base as *mut Header;
+        let capacity = (size - HEADER_SIZE) as u64;
+        let h = unsafe { &mut *hdr };
+        let kept = h.magic == KLOG_MAGIC && h.capacity == capacity;
+        if !kept {
+            h.capacity = capacity;
+            h.head = 0;
+            h.line_start = 1;
+            h.magic = KLOG_MAGIC;
+        }
+        (Self { hdr }, kept)
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*self.hdr }
+    }
+
+    fn header_mut(&mut self) -> &mut Header {
+        unsafe { &mut *self.hdr }
+    }
+
+    fn data(&self) -> *mut u8 {
+        unsafe { (self.hdr as *mut u8).add(HEADER_SIZE) }
+    }
+
+    /// Returns the number of bytes that can be read.
+    fn len(&self) -> usize {
+        let h = self.header();
+        h.head.min(h.capacity) as usize
+    }
+
+    /// Appends bytes, overwriting the oldest ones if the buffer is full.
+    fn push(&mut self, mut bytes: &[u8]) {
+        let data = self.data();
+        let h = self.header_mut();
+        let cap = h.capacity as usize;
+        if bytes.len() > cap {
+            h.head += (bytes.len() - cap) as u64;
+            bytes = &bytes[bytes.len() - cap..];
+        }
+        let pos = (h.head % h.capacity) as usize;
+        let n = bytes.len().min(cap - pos);
+        unsafe {
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), data.add(pos), n);
+            core::ptr::copy_nonoverlapping(bytes[n..].as_ptr(), data, bytes.len() - n);
+        }
+        h.head += bytes.len() as u64;
+        if let Some(&last) = bytes.last() {
+            h.line_start = (last == b'\n') as u64;
+        }
+    }
+
+    /// Appends bytes, prefixing each line with the given timestamp.
+    fn write(&mut self, ts_nanos: u64, bytes: &[u8]) {
+        for line in bytes.split_inclusive(|&b| b == b'\n') {
+            if self.header().line_start != 0 {
+                let secs = ts_nanos / 1_000_000_000;
+                let micros = ts_nanos % 1_000_000_000 / 1_000;
+                let _ = write!(self, "[{secs:>4}.{micros:06}] ");
+            }
+            self.push(line);
+        }
+    }
+
+    /// Copies the latest bytes into `buf`. Returns the number of bytes copied.
+    fn read(&self, buf: &mut [u8]) -> usize {
+        let h = self.header();
+        let cap = h.capacity as usize;
+        let len = self.len().min(buf.len());
+        let pos = ((h.head - len as u64) % h.capacity) as usize;
+        let n = len.min(cap - pos);
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.data().add(pos), buf.as_mut_ptr(), n);
+            core::ptr::copy_nonoverlapping(self.data(), buf[n..].as_mut_ptr(), len - n);
+        }
+        len
+    }
+
+    /// Calls `f` with the contents, from the oldest to the latest.
+    fn for_each_chunk(&self, mut f: impl FnMut(&[u8])) {
+        let h = self.header();
+        let cap = h.capacity as usize;
+        let len = self.len();
+        let pos = ((h.head - len as u64) % h.capacity) as usize;
+        let n = len.min(cap - pos);
+        unsafe {
+            f(core::slice::from_raw_parts(self.data().add(pos), n));
+            f(core::slice::from_raw_parts(self.data(), len - n));
+        }
+    }
+
+    /// Appends the contents of `old`. If `new_boot` is true, i.e., this ring
+    /// buffer holds the output of a previous boot, [`BOOT_SEPARATOR`] is
+    /// appended first on a new line.
+    fn append(&mut self, old: &Ring, new_boot: bool) {
+        if new_boot {
+            if self.header().line_start == 0 {
+                self.push(b"\n");
+            }
+            self.push(BOOT_SEPARATOR);
+        }
+        old.for_each_chunk(|chunk| self.push(chunk));
+        self.header_mut().line_start = old.header().line_start;
+    }
+
+    fn clear(&mut self) {
+        let h = self.header_mut();
+        h.head = 0;
+        h.line_start = 1;
+    }
+}
+
+impl Write for Ring {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[repr(C, align(8))]
+struct DefaultBuffer(UnsafeCell<[u8; DEFAULT_KLOG_SIZE]>);
+
+unsafe impl Sync for DefaultBuffer {}
+
+static DEFAULT_BUFFER: DefaultBuffer = DefaultBuffer(UnsafeCell::new([0; DEFAULT_KLOG_SIZE]));
+
+/// The header of the ring buffer in use, or null before the first write.
+static KLOG: AtomicPtr<Header> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Serializes accesses to the ring buffer. Interrupts are disabled while it is
+/// held, so interrupt handlers on the same CPU do not deadlock on it.
+static KLOG_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+/// Marks that [`KLOG_LOCK`] is not held.
+const NO_OWNER: usize = usize::MAX;
+
+/// The ID of the CPU holding [`KLOG_LOCK`].
+static OWNER: AtomicUsize = AtomicUsize::new(NO_OWNER);
+
+/// Runs `f` with the ring buffer locked, waiting for other CPUs to release
+/// it.
+///
+/// Returns `None` if the lock is already held by the current CPU, i.e., `f` is
+/// re-entered from an exception (e.g., an NMI or a panic) taken while the ring
+/// buffer is being accessed. Before [`set_cpu_id_fn`](super::set_cpu_id_fn) is
+/// called, only the boot CPU is assumed to be running, so all accesses are
+/// taken as from the same CPU.
+fn with_ring<R>(f: impl FnOnce(&mut Ring) -> R) -> Option<R> {
+    let cpu_id = super::current_cpu_id().unwrap_or(0);
+    if OWNER.load(Ordering::Acquire) == cpu_id {
+        return None;
+    }
+    let _guard = KLOG_LOCK.lock();
+    OWNER.store(cpu_id, Ordering::Release);
+    let mut hdr = KLOG.load(Ordering::Acquire);
+    if hdr.is_null() {
+        let base = DEFAULT_BUFFER.0.get() as *mut u8;
+        hdr = unsafe { Ring::init(base, DEFAULT_KLOG_SIZE) }.0.hdr;
+        KLOG.store(hdr, Ordering::Release);
+    }
+    let ret = f(&mut Ring { hdr });
+    OWNER.store(NO_OWNER, Ordering::Release);
+    Some(ret)
+}
+
+/// Appends console output to the ring buffer, prefixing each line with the
+/// current timestamp.
+///
+/// The output is dropped only if it is re-entered on the same CPU while the
+/// ring buffer is being accessed (see [`with_ring`]).
+pub fn write(bytes: &[u8]) {
+    let now = crate::time::monotonic_time_nanos();
+    with_ring(|ring| ring.write(now, bytes));
+}
+
+/// Appends console output that already carries its own timestamps (e.g.,
+/// lines formatted by the console logger) to the ring buffer.
+pub fn write_raw(bytes: &[u8]) {
+    with_ring(|ring| ring.push(bytes));
+}
+
+/// Copies the latest captured output into `buf`.
+///
+/// Returns the number of bytes copied, or 0 if it is re-entered on the same
+/// CPU while the ring buffer is being accessed.
+pub fn read(buf: &mut [u8]) -> usize {
+    with_ring(|ring| ring.read(buf)).unwrap_or(0)
+}
+
+/// Returns the number of bytes that can be read by [`read`].
+pub fn len() -> usize {
+    with_ring(|ring| ring.len()).unwrap_or(0)
+}
+
+/// Writes all captured output to the console.
+///
+/// It is intended for crash dumps, so it does not wait for the lock, and the
+/// output may be inconsistent if the ring buffer is being written.
+pub fn dump() {
+    let hdr = KLOG.load(Ordering::Acquire);
+    if !hdr.is_null() {
+        Ring { hdr }.for_each_chunk(super::write_bytes);
+    }
+}
+
+/// Discards all captured output.
+pub fn clear() {
+    with_ring(|ring| ring.clear());
+}
+
+/// Moves the ring buffer to the given physical memory range.
+///
+/// The range should be one of the reserved ranges reported by
+/// [`reserved_phys_ram_ranges`](crate::mem::reserved_phys_ram_ranges), so that
+/// it is not used by others. The output captured so far is copied to the new
+/// buffer. If the range holds the ring buffer of a previous boot (e.g., before
+/// a warm reset), its contents are kept before the copied output, separated by
+/// a marker line, and it returns `true`. Otherwise, it returns `false`.
+///
+/// # Safety
+///
+/// The range must be 8-byte aligned, mapped by [`phys_to_virt`], and not used
+/// by anything else.
+///
+/// [`phys_to_virt`]: crate::mem::phys_to_virt
+pub unsafe fn set_buffer(range: RawRange) -> bool {
+    let (paddr, size) = range;
+    assert!(size > HEADER_SIZE, "klog buffer is too small");
+    let base = crate::mem::phys_to_virt(paddr.into()).as_mut_ptr();
+    let (mut new_ring, kept) = unsafe { Ring::init(base, size) };
+    with_ring(|old_ring| {
+        if old_ring.hdr != new_ring.hdr {
+            new_ring.append(old_ring, kept);
+            KLOG.store(new_ring.hdr, Ordering::Release);
+        }
+    })
+    .expect("klog buffer is moved in a re-entrant call");
+    kept
+}
+
+/// Moves the ring buffer to a physical memory range reserved for it by the
+/// platform (e.g., given by the platform configuration).
+///
+/// It does nothing and returns `None` if the range is empty or not contained
+/// in the ranges reported by
+/// [`reserved_phys_ram_ranges`](crate::mem::reserved_phys_ram_ranges) (e.g.,
+/// the configured range is not in the RAM of the machine). Otherwise, it
+/// returns whether the contents of a previous boot are kept, like
+/// [`set_buffer`].
+///
+/// # Safety
+///
+/// The range must be used only by the ring buffer, i.e., it must not overlap
+/// other reserved memory (e.g., used by the firmware).
+pub unsafe fn set_reserved_buffer(range: RawRange) -> Option<bool> {
+    if range.1 == 0 || !ranges_contain(crate::mem::reserved_phys_ram_ranges(), range) {
+        return None;
+    }
+    // SAFETY: reserved ranges are mapped, and the range is used only by us.
+    Some(unsafe { set_buffer(range) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HEADER_SIZE, Ring, with_ring};
+
+    fn contents(ring: &Ring) -> Vec<u8> {
+        let mut out = Vec::new();
+        ring.for_each_chunk(|chunk| out.extend_from_slice(chunk));
+        out
+    }
+
+    #[test]
+    fn ring_timestamps() {
+        let mut mem = vec![0u64; (HEADER_SIZE + 64) / 8];
+        let (mut ring, kept) = unsafe { Ring::init(mem.as_mut_ptr() as _, mem.len() * 8) };
+        assert!(!kept);
+
+        ring.write(1_500_000_000, b"ab\ncd");
+        ring.write(2_000_000_000, b"e\n");
+        assert_eq!(contents(&ring), b"[   1.500000] ab\n[   1.500000] cde\n");
+
+        let mut buf = [0; 4];
+        assert_eq!(ring.read(&mut buf), 4);
+        assert_eq!(&buf, b"cde\n");
+    }
+
+    #[test]
+    fn ring_wrap_around() {
+        let mut mem = vec![0u64; (HEADER_SIZE + 8) / 8];
+        let (mut ring, _) = unsafe { Ring::init(mem.as_mut_ptr() as _, mem.len() * 8) };
+
+        ring.push(b"0123456");
+        ring.push(b"789");
+        assert_eq!(ring.len(), 8);
+        assert_eq!(contents(&ring), b"23456789");
+
+        ring.push(b"abcdefghijk");
+        assert_eq!(contents(&ring), b"defghijk");
+
+        let mut buf = [0; 16];
+        assert_eq!(ring.read(&mut buf), 8);
+        assert_eq!(&buf[..8], b"defghijk");
+
+        // the contents are kept by re-initialization
+        let (ring, kept) = unsafe { Ring::init(mem.as_mut_ptr() as _, mem.len() * 8) };
+        assert!(kept);
+        assert_eq!(contents(&ring), b"defghijk");
+    }
+
+    #[test]
+    fn ring_append() {
+        let mut old_mem = vec![0u64; (HEADER_SIZE + 64) / 8];
+        let mut new_mem = vec![0u64; (HEADER_SIZE + 64) / 8];
+        let (mut old, _) = unsafe { Ring::init(old_mem.as_mut_ptr() as _, old_mem.len() * 8) };
+        let (mut new, _) = unsafe { Ring::init(new_mem.as_mut_ptr() as _, new_mem.len() * 8) };
+        old.push(b"boot 2\nab");
+        new.push(b"boot 1");
+
+        new.append(&old, true);
+        assert_eq!(contents(&new), b"boot 1\n---- new boot ----\nboot 2\nab");
+        assert_eq!(new.header().line_start, 0);
+
+        new.clear();
+        new.append(&old, false);
+        assert_eq!(contents(&new), b"boot 2\nab");
+    }
+
+    #[test]
+    fn reentrant_access() {
+        // before `set_cpu_id_fn` is called
+        assert_eq!(with_ring(|_| with_ring(|_| ())), Some(None));
+        assert_eq!(with_ring(|_| ()), Some(()));
+
+        crate::console::set_cpu_id_fn(|| 1);
+        assert_eq!(with_ring(|_| with_ring(|_| ())), Some(None));
+        assert_eq!(with_ring(|_| ()), Some(()));
+    }
+}
@@ -0,0 +1,166 @@
+//! A [`log`] crate backend over [`ConsoleIf`](super::ConsoleIf).
+
+use core::fmt::{self, Write};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// The size of the buffer used to batch console writes.
+const LINE_BUF_SIZE: usize = 256;
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// Collects formatted output and passes it to `output` in batches, so that a
+/// short log line is written to the console by a single call and is less
+/// likely to interleave with lines from other CPUs.
+struct LineWriter<F: FnMut(&[u8])> {
+    buf: [u8; LINE_BUF_SIZE],
+    len: usize,
+    output: F,
+}
+
+impl<F: FnMut(&[u8])> LineWriter<F> {
+    const fn new(output: F) -> Self {
+        Self {
+            buf: [0; LINE_BUF_SIZE],
+            len: 0,
+            output,
+        }
+    }
+
+    fn flush(&mut self) {
+        (self.output)(&self.buf[..self.len]);
+        self.len = 0;
+    }
+}
+
+impl<F: FnMut(&[u8])> Write for LineWriter<F> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            if self.len == LINE_BUF_SIZE {
+                self.flush();
+            }
+            let n = bytes.len().min(LINE_BUF_SIZE - self.len);
+            self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+            self.len += n;
+            bytes = &bytes[n..];
+        }
+        Ok(())
+    }
+}
+
+/// Returns the ANSI color code of the given log level.
+const fn level_color(level: Level) -> u8 {
+    match level {
+        Level::Error => 31, // red
+        Level::Warn => 93,  // bright yellow
+        Level::Info => 32,  // green
+        Level::Debug => 36, // cyan
+        Level::Trace => 90, // bright black
+    }
+}
+
+/// Copies `bytes` into `buf` without the ANSI color escapes (`ESC [ ... m`),
+/// and returns the copied bytes.
+#[cfg(feature = "klog")]
+fn strip_colors<'a>(bytes: &[u8], buf: &'a mut [u8; LINE_BUF_SIZE]) -> &'a [u8] {
+    let mut len = 0;
+    let mut in_escape = false;
+    for &b in bytes {
+        match b {
+            0x1b => in_escape = true,
+            b'm' if in_escape => in_escape = false,
+            _ if in_escape => {}
+            _ => {
+                buf[len] = b;
+                len += 1;
+            }
+        }
+    }
+    &buf[..len]
+}
+
+struct ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let now = crate::time::monotonic_time();
+        let level = record.level();
+        let mut w = LineWriter::new(|bytes: &[u8]| {
+            super::write_bytes(bytes);
+            // The color escapes are in the line prefix, which is never split
+            // across batches.
+            #[cfg(feature = "klog")]
+            super::klog::write_raw(strip_colors(bytes, &mut [0; LINE_BUF_SIZE]));
+        });
+        let _ = write!(w, "[{:>4}.{:06} ", now.as_secs(), now.subsec_micros());
+        if let Some(cpu_id) = super::current_cpu_id() {
+            let _ = write!(w, "{cpu_id} ");
+        }
+        let _ = writeln!(
+            w,
+            "\x1b[{}m{:<5}\x1b[0m {}] {}",
+            level_color(level),
+            level,
+            record.target(),
+            record.args()
+        );
+        w.flush();
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the console logger as the global logger of the [`log`] crate.
+///
+/// Log records up to `max_level` are printed to the console with the
+/// [`monotonic_time`](crate::time::monotonic_time) timestamp, the CPU ID (once
+/// the platform or the kernel calls [`set_cpu_id_fn`](super::set_cpu_id_fn))
+/// and a colored level tag.
+///
+/// It returns an error if a global logger has already been installed.
+pub fn init_logger(max_level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LINE_BUF_SIZE, LineWriter};
+    use core::fmt::Write;
+
+    #[test]
+    fn line_writer() {
+        let mut out = Vec::new();
+        let mut w = LineWriter::new(|bytes: &[u8]| out.push(bytes.to_vec()));
+        write!(w, "abc{}", 123).unwrap();
+        w.flush();
+        assert_eq!(out, [b"abc123".to_vec()]);
+    }
+
+    #[test]
+    fn line_writer_overflow() {
+        let mut out = Vec::new();
+        let mut w = LineWriter::new(|bytes: &[u8]| out.push(bytes.len()));
+        w.write_str(&"x".repeat(LINE_BUF_SIZE + 1)).unwrap();
+        w.flush();
+        assert_eq!(out, [LINE_BUF_SIZE, 1]);
+    }
+
+    #[cfg(feature = "klog")]
+    #[test]
+    fn strip_colors() {
+        let mut buf = [0; LINE_BUF_SIZE];
+        let line = b"[   1.000000 0 \x1b[32mINFO \x1b[0m axplat] ok\n";
+        let stripped = super::strip_colors(line, &mut buf);
+        assert_eq!(stripped, b"[   1.000000 0 INFO  axplat] ok\n");
+    }
+}
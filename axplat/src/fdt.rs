@@ -0,0 +1,708 @@
+//! Minimal flattened device tree (FDT) parser.
+//!
+//! It only walks the structure block of a device tree blob (DTB) without any
+//! allocation, which is enough for platforms to discover the memory layout
+//! (`/memory`, `/reserved-memory`, `/chosen`) at early boot.
+//!
+//! Specification: <https://devicetree-specification.readthedocs.io/en/stable/flattened-format.html>
+
+use core::{ffi::CStr, fmt};
+
+use crate::mem::RawRange;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_HEADER_SIZE: usize = 40;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// Default `#address-cells` if the property is absent.
+const DEFAULT_ADDRESS_CELLS: usize = 2;
+/// Default `#size-cells` if the property is absent.
+const DEFAULT_SIZE_CELLS: usize = 1;
+
+/// The error type for device tree parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtError {
+    /// The blob does not start with the FDT magic number.
+    BadMagic,
+    /// The blob version is not compatible with version 16.
+    BadVersion,
+    /// The blob is shorter than the size declared in its header, or some
+    /// block lies outside of it.
+    Truncated,
+}
+
+impl fmt::Display for FdtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "bad FDT magic"),
+            Self::BadVersion => write!(f, "unsupported FDT version"),
+            Self::Truncated => write!(f, "truncated FDT blob"),
+        }
+    }
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn be64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a big-endian number made of `cells` 32-bit cells.
+fn read_cells(data: &[u8], cells: usize) -> Option<u64> {
+    (0..cells).try_fold(0u64, |acc, i| Some((acc << 32) | be32(data, i * 4)? as u64))
+}
+
+/// A parsed flattened device tree.
+#[derive(Clone, Copy)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    struct_block: &'a [u8],
+    strings_block: &'a [u8],
+    rsvmap_offset: usize,
+}
+
+impl<'a> Fdt<'a> {
+    /// Parses the device tree blob from the given bytes.
+    ///
+    /// The slice can be longer than the blob, the extra bytes are ignored.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, FdtError> {
+        let header = |idx: usize| be32(data, idx * 4).ok_or(FdtError::Truncated);
+        if header(0)? != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = header(1)? as usize;
+        if total_size < FDT_HEADER_SIZE || total_size > data.len() {
+            return Err(FdtError::Truncated);
+        }
+        if header(7)? > FDT_LAST_COMP_VERSION || header(6)? < FDT_LAST_COMP_VERSION {
+            return Err(FdtError::BadVersion);
+        }
+        let data = &data[..total_size];
+        let block = |offset: u32, size: u32| {
+            let (offset, size) = (offset as usize, size as usize);
+            offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .ok_or(FdtError::Truncated)
+        };
+        Ok(Self {
+            data,
+            struct_block: block(header(2)?, header(9)?)?,
+            strings_block: block(header(3)?, header(8)?)?,
+            rsvmap_offset: header(4)? as usize,
+        })
+    }
+
+    /// Parses the device tree blob located at the given address.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a readable memory region that contains the whole
+    /// blob, and the region must remain valid and unchanged for `'static`.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Result<Fdt<'static>, FdtError> {
+        if ptr.is_null() {
+            return Err(FdtError::BadMagic);
+        }
+        let header = unsafe { core::slice::from_raw_parts(ptr, FDT_HEADER_SIZE) };
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = be32(header, 4).unwrap() as usize;
+        Fdt::from_bytes(unsafe { core::slice::from_raw_parts(ptr, total_size) })
+    }
+
+    /// Returns the total size in bytes of the blob.
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the root node `/`.
+    pub fn root(&self) -> Node<'a> {
+        Node {
+            fdt: *self,
+            name: "",
+            offset: self.node_body(0).unwrap_or(self.struct_block.len()),
+        }
+    }
+
+    /// Finds a node by its full path (e.g., `/soc/serial@10000000`).
+    ///
+    /// The unit address can be omitted if it's unambiguous (e.g., `/memory`
+    /// matches `/memory@80000000`).
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        path.split('/')
+            .filter(|s| !s.is_empty())
+            .try_fold(self.root(), |node, name| node.child(name))
+    }
+
+    /// Returns the `/chosen` node.
+    pub fn chosen(&self) -> Option<Chosen<'a>> {
+        self.find_node("/chosen").map(Chosen)
+    }
+
+    /// Returns all physical memory (RAM) ranges described by the `/memory`
+    /// nodes.
+    pub fn memory_regions(&self) -> impl Iterator<Item = RawRange> + use<'a> {
+        let root = self.root();
+        let (addr_cells, size_cells) = root.cells();
+        root.children()
+            .filter(|node| {
+                node.base_name() == "memory"
+                    || node.property("device_type").and_then(|p| p.as_str()) == Some("memory")
+            })
+            .flat_map(move |node| node.reg_with(addr_cells, size_cells))
+    }
+
+    /// Returns all reserved physical memory ranges, including the entries in
+    /// the memory reservation block and the children of `/reserved-memory`.
+    ///
+    /// Dynamically allocated reserved regions (with only a `size` property)
+    /// are not included.
+    pub fn reserved_regions(&self) -> impl Iterator<Item = RawRange> + use<'a> {
+        let rsvmap = MemReserveIter {
+            data: self.data,
+            offset: self.rsvmap_offset,
+        };
+        let reserved_memory = self.find_node("/reserved-memory");
+        let nodes = reserved_memory.into_iter().flat_map(|parent| {
+            let (addr_cells, size_cells) = parent.cells();
+            parent
+                .children()
+                .flat_map(move |node| node.reg_with(addr_cells, size_cells))
+        });
+        rsvmap.chain(nodes)
+    }
+
+    /// Returns all reserved physical memory ranges (see [`reserved_regions`]),
+    /// and the range of the blob itself, which is loaded at `paddr` and should
+    /// not be overwritten either.
+    ///
+    /// [`reserved_regions`]: Self::reserved_regions
+    pub fn reserved_regions_with_blob(
+        &self,
+        paddr: usize,
+    ) -> impl Iterator<Item = RawRange> + use<'a> {
+        self.reserved_regions().chain([(paddr, self.total_size())])
+    }
+
+    /// Returns the offset of the first token after the `FDT_BEGIN_NODE` token
+    /// (and its name) at `offset`.
+    fn node_body(&self, offset: usize) -> Option<usize> {
+        let mut tokens = Tokens::new(self, offset);
+        match tokens.next()? {
+            Token::BeginNode(_) => Some(tokens.offset),
+            _ => None,
+        }
+    }
+
+    fn string_at(&self, offset: usize) -> Option<&'a str> {
+        let bytes = self.strings_block.get(offset..)?;
+        CStr::from_bytes_until_nul(bytes).ok()?.to_str().ok()
+    }
+}
+
+impl fmt::Debug for Fdt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fdt")
+            .field("total_size", &self.total_size())
+            .finish()
+    }
+}
+
+/// Iterator over the memory reservation block.
+struct MemReserveIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl Iterator for MemReserveIter<'_> {
+    type Item = RawRange;
+
+    fn next(&mut self) -> Option<RawRange> {
+        let addr = be64(self.data, self.offset)?;
+        let size = be64(self.data, self.offset + 8)?;
+        if addr == 0 && size == 0 {
+            return None;
+        }
+        self.offset += 16;
+        Some((addr as usize, size as usize))
+    }
+}
+
+enum Token<'a> {
+    BeginNode(&'a str),
+    EndNode,
+    Prop(Property<'a>),
+}
+
+/// Iterator over the tokens in the structure block, skipping `FDT_NOP`.
+struct Tokens<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(fdt: &Fdt<'a>, offset: usize) -> Self {
+        Self { fdt: *fdt, offset }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let data = self.fdt.struct_block;
+        loop {
+            let token = be32(data, self.offset)?;
+            self.offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = CStr::from_bytes_until_nul(data.get(self.offset..)?).ok()?;
+                    self.offset += (name.count_bytes() + 1).next_multiple_of(4);
+                    return Some(Token::BeginNode(name.to_str().ok()?));
+                }
+                FDT_END_NODE => return Some(Token::EndNode),
+                FDT_PROP => {
+                    let len = be32(data, self.offset)? as usize;
+                    let name_off = be32(data, self.offset + 4)? as usize;
+                    let value = data.get(self.offset + 8..self.offset + 8 + len)?;
+                    self.offset += (8 + len).next_multiple_of(4);
+                    return Some(Token::Prop(Property {
+                        name: self.fdt.string_at(name_off)?,
+                        value,
+                    }));
+                }
+                FDT_NOP => continue,
+                FDT_END => return None,
+                _ => return None, // unknown token, treat as the end
+            }
+        }
+    }
+}
+
+/// A device tree property.
+#[derive(Debug, Clone, Copy)]
+pub struct Property<'a> {
+    /// The property name.
+    pub name: &'a str,
+    /// The raw property value.
+    pub value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    /// Interprets the value as a NUL-terminated string.
+    pub fn as_str(&self) -> Option<&'a str> {
+        CStr::from_bytes_until_nul(self.value).ok()?.to_str().ok()
+    }
+
+    /// Interprets the value as a list of NUL-terminated strings (e.g.,
+    /// `compatible`).
+    pub fn as_str_list(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.value
+            .split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    /// Interprets the value as a single 32-bit cell.
+    pub fn as_u32(&self) -> Option<u32> {
+        be32(self.value, 0)
+    }
+
+    /// Interprets the value as a number, which can be either one or two cells
+    /// long.
+    pub fn as_usize(&self) -> Option<usize> {
+        match self.value.len() {
+            4 => self.as_u32().map(|v| v as usize),
+            8 => be64(self.value, 0).map(|v| v as usize),
+            _ => None,
+        }
+    }
+}
+
+/// A device tree node.
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+    name: &'a str,
+    /// Offset of the first token inside the node.
+    offset: usize,
+}
+
+impl<'a> Node<'a> {
+    /// Returns the full node name, including the unit address (e.g.,
+    /// `memory@80000000`).
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the node name without the unit address (e.g., `memory`).
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap_or_default()
+    }
+
+    /// Returns an iterator over the properties of the node.
+    pub fn properties(&self) -> impl Iterator<Item = Property<'a>> + use<'a> {
+        Tokens::new(&self.fdt, self.offset).map_while(|token| match token {
+            Token::Prop(prop) => Some(prop),
+            _ => None,
+        })
+    }
+
+    /// Finds a property by its name.
+    pub fn property(&self, name: &str) -> Option<Property<'a>> {
+        self.properties().find(|prop| prop.name == name)
+    }
+
+    /// Returns an iterator over the direct children of the node.
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> + use<'a> {
+        let fdt = self.fdt;
+        let mut tokens = Tokens::new(&fdt, self.offset);
+        let mut depth = 0usize;
+        core::iter::from_fn(move || {
+            loop {
+                match tokens.next()? {
+                    Token::BeginNode(name) => {
+                        depth += 1;
+                        if depth == 1 {
+                            return Some(Node {
+                                fdt,
+                                name,
+                                offset: tokens.offset,
+                            });
+                        }
+                    }
+                    Token::EndNode => {
+                        depth = depth.checked_sub(1)?;
+                    }
+                    Token::Prop(_) => {}
+                }
+            }
+        })
+    }
+
+    /// Finds a direct child by its name.
+    ///
+    /// The unit address can be omitted if the name contains no `@`.
+    pub fn child(&self, name: &str) -> Option<Node<'a>> {
+        let exact = name.contains('@');
+        self.children().find(|node| {
+            if exact {
+                node.name == name
+            } else {
+                node.base_name() == name
+            }
+        })
+    }
+
+    /// Returns the `compatible` strings of the node.
+    pub fn compatible(&self) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.property("compatible")
+            .into_iter()
+            .flat_map(|prop| prop.as_str_list())
+    }
+
+    /// Returns the `#address-cells` and `#size-cells` that this node
+    /// specifies for its children.
+    pub fn cells(&self) -> (usize, usize) {
+        let cells = |name, default| {
+            self.property(name)
+                .and_then(|p| p.as_u32())
+                .map_or(default, |v| v as usize)
+        };
+        (
+            cells("#address-cells", DEFAULT_ADDRESS_CELLS),
+            cells("#size-cells", DEFAULT_SIZE_CELLS),
+        )
+    }
+
+    /// Returns the `(address, size)` pairs of the `reg` property, decoded
+    /// with the given `#address-cells` and `#size-cells` of the parent node.
+    pub fn reg_with(
+        &self,
+        addr_cells: usize,
+        size_cells: usize,
+    ) -> impl Iterator<Item = RawRange> + use<'a> {
+        let entry_size = (addr_cells + size_cells) * 4;
+        let value = match self.property("reg") {
+            Some(prop) if entry_size > 0 => prop.value,
+            _ => &[],
+        };
+        value
+            .chunks_exact(entry_size.max(1))
+            .filter_map(move |entry| {
+                let addr = read_cells(entry, addr_cells)?;
+                let size = read_cells(&entry[addr_cells * 4..], size_cells)?;
+                Some((addr as usize, size as usize))
+            })
+    }
+}
+
+impl fmt::Debug for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node").field("name", &self.name).finish()
+    }
+}
+
+/// The `/chosen` node, which carries the parameters passed by the bootloader.
+#[derive(Debug, Clone, Copy)]
+pub struct Chosen<'a>(Node<'a>);
+
+impl<'a> Chosen<'a> {
+    /// Returns the underlying node.
+    pub fn node(&self) -> Node<'a> {
+        self.0
+    }
+
+    /// Returns the kernel command line (`bootargs`).
+    pub fn bootargs(&self) -> Option<&'a str> {
+        self.0.property("bootargs").and_then(|p| p.as_str())
+    }
+
+    /// Returns the path of the console device (`stdout-path`), without the
+    /// options after `:`.
+    pub fn stdout_path(&self) -> Option<&'a str> {
+        let path = self.0.property("stdout-path")?.as_str()?;
+        path.split(':').next()
+    }
+
+    /// Returns the physical memory range of the initial ramdisk, from the
+    /// `linux,initrd-start` and `linux,initrd-end` properties.
+    pub fn initrd(&self) -> Option<RawRange> {
+        let start = self.0.property("linux,initrd-start")?.as_usize()?;
+        let end = self.0.property("linux,initrd-end")?.as_usize()?;
+        (end > start).then_some((start, end - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tiny DTB builder for tests.
+    #[derive(Default)]
+    struct Builder {
+        structs: Vec<u8>,
+        strings: Vec<u8>,
+        rsvmap: Vec<(u64, u64)>,
+    }
+
+    impl Builder {
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.structs.extend(FDT_BEGIN_NODE.to_be_bytes());
+            self.structs.extend(name.as_bytes());
+            self.structs.push(0);
+            self.pad();
+            self
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.structs.extend(FDT_END_NODE.to_be_bytes());
+            self
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let name_off = self.strings.len() as u32;
+            self.strings.extend(name.as_bytes());
+            self.strings.push(0);
+            self.structs.extend(FDT_PROP.to_be_bytes());
+            self.structs.extend((value.len() as u32).to_be_bytes());
+            self.structs.extend(name_off.to_be_bytes());
+            self.structs.extend(value);
+            self.pad();
+            self
+        }
+
+        fn prop_cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+            let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+            self.prop(name, &value)
+        }
+
+        fn prop_str(&mut self, name: &str, s: &str) -> &mut Self {
+            let mut value = s.as_bytes().to_vec();
+            value.push(0);
+            self.prop(name, &value)
+        }
+
+        fn nop(&mut self) -> &mut Self {
+            self.structs.extend(FDT_NOP.to_be_bytes());
+            self
+        }
+
+        fn pad(&mut self) {
+            while !self.structs.len().is_multiple_of(4) {
+                self.structs.push(0);
+            }
+        }
+
+        fn build(&mut self) -> Vec<u8> {
+            self.structs.extend(FDT_END.to_be_bytes());
+            let rsvmap_off = FDT_HEADER_SIZE;
+            let struct_off = rsvmap_off + (self.rsvmap.len() + 1) * 16;
+            let strings_off = struct_off + self.structs.len();
+            let total = strings_off + self.strings.len();
+
+            let mut blob = Vec::new();
+            for v in [
+                FDT_MAGIC,
+                total as u32,
+                struct_off as u32,
+                strings_off as u32,
+                rsvmap_off as u32,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structs.len() as u32,
+            ] {
+                blob.extend(v.to_be_bytes());
+            }
+            for &(addr, size) in self.rsvmap.iter().chain([&(0, 0)]) {
+                blob.extend(addr.to_be_bytes());
+                blob.extend(size.to_be_bytes());
+            }
+            blob.extend(&self.structs);
+            blob.extend(&self.strings);
+            blob
+        }
+    }
+
+    fn sample_dtb() -> Vec<u8> {
+        let mut b = Builder::default();
+        b.rsvmap.push((0x8000_0000, 0x4_0000));
+        b.begin("")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .prop_str("compatible", "riscv-virtio")
+            .begin("chosen")
+            .prop_str("bootargs", "console=ttyS0 quiet")
+            .prop_str("stdout-path", "/soc/serial@10000000:115200")
+            .prop_cells("linux,initrd-start", &[0x8800_0000])
+            .prop_cells("linux,initrd-end", &[0x8810_0000])
+            .end()
+            .nop()
+            .begin("memory@80000000")
+            .prop_str("device_type", "memory")
+            .prop_cells("reg", &[0x0, 0x8000_0000, 0x0, 0x4000_0000])
+            .end()
+            .begin("memory@100000000")
+            .prop_str("device_type", "memory")
+            .prop_cells("reg", &[0x1, 0x0, 0x0, 0x4000_0000])
+            .end()
+            .begin("reserved-memory")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .prop("ranges", &[])
+            .begin("mmode_resv0@80040000")
+            .prop_cells("reg", &[0x0, 0x8004_0000, 0x0, 0x2_0000])
+            .prop("no-map", &[])
+            .end()
+            .begin("dynamic")
+            .prop_cells("size", &[0x0, 0x10_0000])
+            .end()
+            .end()
+            .begin("soc")
+            .prop_cells("#address-cells", &[2])
+            .prop_cells("#size-cells", &[2])
+            .begin("serial@10000000")
+            .prop("compatible", b"ns16550a\0snps,dw-apb-uart\0")
+            .prop_cells("reg", &[0x0, 0x1000_0000, 0x0, 0x100])
+            .end()
+            .end()
+            .end()
+            .build()
+    }
+
+    #[test]
+    fn header() {
+        let dtb = sample_dtb();
+        let fdt = Fdt::from_bytes(&dtb).unwrap();
+        assert_eq!(fdt.total_size(), dtb.len());
+
+        let mut bad = dtb.clone();
+        bad[0] = 0;
+        assert_eq!(Fdt::from_bytes(&bad).unwrap_err(), FdtError::BadMagic);
+        assert_eq!(
+            Fdt::from_bytes(&dtb[..dtb.len() - 1]).unwrap_err(),
+            FdtError::Truncated
+        );
+        assert_eq!(
+            unsafe { Fdt::from_ptr(dtb.as_ptr()) }.unwrap().total_size(),
+            dtb.len()
+        );
+    }
+
+    #[test]
+    fn nodes() {
+        let dtb = sample_dtb();
+        let fdt = Fdt::from_bytes(&dtb).unwrap();
+        let root = fdt.root();
+        assert_eq!(root.cells(), (2, 2));
+        assert_eq!(root.compatible().collect::<Vec<_>>(), ["riscv-virtio"]);
+        assert_eq!(
+            root.children().map(|n| n.name()).collect::<Vec<_>>(),
+            [
+                "chosen",
+                "memory@80000000",
+                "memory@100000000",
+                "reserved-memory",
+                "soc"
+            ]
+        );
+
+        let serial = fdt.find_node("/soc/serial@10000000").unwrap();
+        assert_eq!(
+            serial.compatible().collect::<Vec<_>>(),
+            ["ns16550a", "snps,dw-apb-uart"]
+        );
+        assert_eq!(
+            serial.reg_with(2, 2).collect::<Vec<_>>(),
+            [(0x1000_0000, 0x100)]
+        );
+        assert_eq!(fdt.find_node("/soc/serial").unwrap().name(), serial.name());
+        assert!(fdt.find_node("/soc/serial@0").is_none());
+        assert!(fdt.find_node("/cpus").is_none());
+    }
+
+    #[test]
+    fn memory() {
+        let dtb = sample_dtb();
+        let fdt = Fdt::from_bytes(&dtb).unwrap();
+        assert_eq!(
+            fdt.memory_regions().collect::<Vec<_>>(),
+            [(0x8000_0000, 0x4000_0000), (0x1_0000_0000, 0x4000_0000)]
+        );
+        assert_eq!(
+            fdt.reserved_regions().collect::<Vec<_>>(),
+            [(0x8000_0000, 0x4_0000), (0x8004_0000, 0x2_0000)]
+        );
+        assert_eq!(
+            fdt.reserved_regions_with_blob(0x9000_0000)
+                .collect::<Vec<_>>(),
+            [
+                (0x8000_0000, 0x4_0000),
+                (0x8004_0000, 0x2_0000),
+                (0x9000_0000, dtb.len())
+            ]
+        );
+    }
+
+    #[test]
+    fn chosen() {
+        let dtb = sample_dtb();
+        let chosen = Fdt::from_bytes(&dtb).unwrap().chosen().unwrap();
+        assert_eq!(chosen.bootargs(), Some("console=ttyS0 quiet"));
+        assert_eq!(chosen.stdout_path(), Some("/soc/serial@10000000"));
+        assert_eq!(chosen.initrd(), Some((0x8800_0000, 0x10_0000)));
+    }
+}
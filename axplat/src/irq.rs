@@ -30,3 +30,38 @@ pub trait IrqIf {
     /// also acknowledges the interrupt controller after handling.
     fn handle(irq: usize);
 }
+
+/// The target of an inter-processor interrupt (IPI).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpiTarget {
+    /// Send to the specified CPU.
+    Cpu {
+        /// The ID of the target CPU.
+        cpu_id: usize,
+    },
+    /// Send to all CPUs except the current one.
+    AllExceptCurrent {
+        /// The ID of the current CPU.
+        cpu_id: usize,
+        /// The number of CPUs.
+        cpu_num: usize,
+    },
+    /// Send to all CPUs, including the current one.
+    All {
+        /// The number of CPUs.
+        cpu_num: usize,
+    },
+}
+
+/// Inter-processor interrupt (IPI) interface.
+#[def_plat_interface]
+pub trait IpiIf {
+    /// Returns the IRQ number of IPIs.
+    ///
+    /// The handler of IPIs should be registered with this IRQ number via
+    /// [`register`].
+    fn ipi_irq_num() -> usize;
+
+    /// Sends an IPI to the given target CPU(s).
+    fn send_ipi(target: IpiTarget);
+}
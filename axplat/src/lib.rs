@@ -5,6 +5,7 @@
 extern crate axplat_macros;
 
 pub mod console;
+pub mod fdt;
 pub mod init;
 pub mod irq;
 pub mod mem;
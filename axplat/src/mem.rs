@@ -2,7 +2,8 @@
 
 use core::{fmt, ops::Range};
 
-use memory_addr::PhysAddr;
+use heapless::Vec;
+use memory_addr::{PhysAddr, VirtAddr};
 
 bitflags::bitflags! {
     /// The flags of a physical memory region.
@@ -47,6 +48,16 @@ pub const DEFAULT_MMIO_FLAGS: MemRegionFlags = MemRegionFlags::READ
     .union(MemRegionFlags::DEVICE)
     .union(MemRegionFlags::RESERVED);
 
+/// The default flags for the kernel image region (readable, writable,
+/// executable, and reserved).
+pub const DEFAULT_KERNEL_FLAGS: MemRegionFlags = MemRegionFlags::READ
+    .union(MemRegionFlags::WRITE)
+    .union(MemRegionFlags::EXECUTE)
+    .union(MemRegionFlags::RESERVED);
+
+/// The maximum number of regions returned by [`memory_regions`].
+pub const MAX_MEM_REGIONS: usize = 128;
+
 /// The raw memory range with start and size.
 pub type RawRange = (usize, usize);
 
@@ -115,6 +126,37 @@ pub trait MemIf {
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange];
+
+    /// Translates a physical address to a virtual address.
+    ///
+    /// It is just an easy way to access physical memory when virtual memory
+    /// is enabled. The mapping may not be established yet.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr;
+
+    /// Translates a virtual address to a physical address.
+    ///
+    /// It is a reverse operation of [`phys_to_virt`]. It requires that the
+    /// virtual address is in the linear mapping.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr;
+
+    /// Returns the offset between bus addresses and physical addresses.
+    ///
+    /// On some boards, devices (e.g., DMA masters) see the memory at a
+    /// different address from the CPU, where `bus_addr = paddr + offset`.
+    fn phys_bus_offset() -> usize;
+}
+
+/// Converts a physical address to a bus address, which is the address seen by
+/// devices (e.g., for DMA).
+pub fn phys_to_bus(paddr: PhysAddr) -> usize {
+    paddr.as_usize().wrapping_add(phys_bus_offset())
+}
+
+/// Converts a bus address seen by devices to a physical address.
+///
+/// It is a reverse operation of [`phys_to_bus`].
+pub fn bus_to_phys(bus_addr: usize) -> PhysAddr {
+    PhysAddr::from_usize(bus_addr.wrapping_sub(phys_bus_offset()))
 }
 
 /// Returns the total size of physical memory (RAM) on the platform.
@@ -125,6 +167,85 @@ pub fn total_ram_size() -> usize {
     phys_ram_ranges().iter().map(|range| range.1).sum()
 }
 
+/// Returns all physical memory regions on the platform, sorted by the start
+/// address.
+///
+/// It combines the ranges returned by [`phys_ram_ranges`],
+/// [`reserved_phys_ram_ranges`] and [`mmio_ranges`]:
+///
+/// - RAM not covered by reserved ranges or the kernel image is yielded as
+///   `"free memory"` with [`DEFAULT_RAM_FLAGS`].
+/// - Reserved ranges are yielded as `"reserved"` with
+///   [`DEFAULT_RESERVED_FLAGS`].
+/// - MMIO ranges are yielded as `"mmio"` with [`DEFAULT_MMIO_FLAGS`].
+/// - `kernel_image` (the physical range where the kernel is loaded) is yielded
+///   as `"kernel image"` with [`DEFAULT_KERNEL_FLAGS`].
+///
+/// Empty ranges are skipped.
+///
+/// # Panics
+///
+/// Panics if the RAM ranges overlap, or if there are more than
+/// [`MAX_MEM_REGIONS`] regions.
+pub fn memory_regions(kernel_image: RawRange) -> impl Iterator<Item = PhysMemRegion> {
+    collect_regions(
+        phys_ram_ranges(),
+        reserved_phys_ram_ranges(),
+        mmio_ranges(),
+        kernel_image,
+    )
+    .into_iter()
+}
+
+fn collect_regions(
+    ram: &[RawRange],
+    reserved: &[RawRange],
+    mmio: &[RawRange],
+    kernel_image: RawRange,
+) -> Vec<PhysMemRegion, MAX_MEM_REGIONS> {
+    let mut regions = Vec::new();
+    let mut push = |r: PhysMemRegion| {
+        if r.size > 0 {
+            regions.push(r).expect("too many memory regions");
+        }
+    };
+
+    push(PhysMemRegion {
+        paddr: PhysAddr::from_usize(kernel_image.0),
+        size: kernel_image.1,
+        flags: DEFAULT_KERNEL_FLAGS,
+        name: "kernel image",
+    });
+    for &(start, size) in mmio {
+        push(PhysMemRegion::new_mmio(start, size, "mmio"));
+    }
+    for &(start, size) in reserved {
+        push(PhysMemRegion::new_reserved(start, size, "reserved"));
+    }
+
+    // Remove the kernel image and all reserved ranges from RAM ranges, and
+    // push the remaining as free memory. The excluded ranges are merged, as
+    // reserved ranges may overlap each other or the kernel image.
+    let mut exclude: Vec<RawRange, MAX_MEM_REGIONS> = Vec::new();
+    for &r in reserved.iter().chain([&kernel_image]) {
+        exclude.push(r).expect("too many reserved ranges");
+    }
+    let len = merge_ranges(&mut exclude);
+    exclude.truncate(len);
+    let mut sorted_ram: Vec<RawRange, MAX_MEM_REGIONS> = Vec::new();
+    sorted_ram
+        .extend_from_slice(ram)
+        .expect("too many RAM ranges");
+    sorted_ram.sort_unstable_by_key(|r| r.0);
+    ranges_difference(&sorted_ram, &exclude, |(start, size)| {
+        push(PhysMemRegion::new_ram(start, size, "free memory"));
+    })
+    .unwrap_or_else(|(a, b)| panic!("RAM ranges overlap: {a:#x?} and {b:#x?}"));
+
+    regions.sort_unstable_by_key(|r| r.paddr);
+    regions
+}
+
 /// The error type for overlapping check.
 ///
 /// It contains the overlapping range pair.
@@ -160,6 +281,24 @@ pub fn check_sorted_ranges_overlap(
     Ok(())
 }
 
+/// Checks if `range` is entirely contained in one of the given ranges.
+///
+/// # Example
+///
+/// ```rust
+/// # use axplat::mem::ranges_contain;
+/// assert!(ranges_contain(&[(0, 10), (20, 10)], (22, 8)));
+/// assert!(!ranges_contain(&[(0, 10), (20, 10)], (5, 20)));
+/// ```
+pub fn ranges_contain(ranges: &[RawRange], (start, size): RawRange) -> bool {
+    let Some(end) = start.checked_add(size) else {
+        return false;
+    };
+    ranges
+        .iter()
+        .any(|&(s, len)| start >= s && end <= s.saturating_add(len))
+}
+
 /// Removes a portion of ranges from the given ranges.
 ///
 /// `from` is a list of ranges to be operated on, and `exclude` is a list of
@@ -211,8 +350,66 @@ where
     Ok(())
 }
 
+/// Sorts the ranges by the start, and merges the overlapping or adjacent ones.
+///
+/// The merged ranges are moved to the beginning of `ranges`, and their number
+/// is returned.
+///
+/// # Example
+///
+/// ```rust
+/// # use axplat::mem::merge_ranges;
+/// let mut ranges = [(20, 10), (0, 10), (5, 10), (30, 5)];
+/// let n = merge_ranges(&mut ranges);
+/// assert_eq!(&ranges[..n], &[(0, 15), (20, 15)]);
+/// ```
+pub fn merge_ranges(ranges: &mut [RawRange]) -> usize {
+    ranges.sort_unstable_by_key(|r| r.0);
+    let mut len = 0;
+    for i in 0..ranges.len() {
+        let (start, size) = ranges[i];
+        if len > 0 {
+            let last = &mut ranges[len - 1];
+            if start <= last.0 + last.1 {
+                last.1 = last.1.max(start + size - last.0);
+                continue;
+            }
+        }
+        ranges[len] = (start, size);
+        len += 1;
+    }
+    len
+}
+
+/// Sorts and merges the given ranges (see [`merge_ranges`]), then removes
+/// `exclude` from them.
+///
+/// It is usually used to build the reserved ranges, which must not contain the
+/// kernel image. `exclude` should have been sorted by the start, and have
+/// non-overlapping ranges. `result_op` is called for each resulting range.
+///
+/// # Example
+///
+/// ```rust
+/// # use axplat::mem::normalize_ranges;
+/// let mut res = Vec::new();
+/// // 0..10, 5..15, 30..40 - 12..32 = 0..12, 32..40
+/// normalize_ranges(&mut [(30, 10), (0, 10), (5, 10)], &[(12, 20)], |r| res.push(r));
+/// assert_eq!(res, &[(0, 12), (32, 8)]);
+/// ```
+pub fn normalize_ranges<F>(ranges: &mut [RawRange], exclude: &[RawRange], result_op: F)
+where
+    F: FnMut(RawRange),
+{
+    let len = merge_ranges(ranges);
+    // merged ranges never overlap
+    ranges_difference(&ranges[..len], exclude, result_op).unwrap();
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{DEFAULT_KERNEL_FLAGS, MemRegionFlags};
+
     #[test]
     fn check_sorted_ranges_overlap() {
         use super::check_sorted_ranges_overlap as f;
@@ -272,4 +469,69 @@ mod tests {
         // 10..20
         assert_eq!(f(&[(10, 10)], &[(0, 30)]), &[]); // - 0..30 = []
     }
+
+    #[test]
+    fn merge_ranges() {
+        let f = |ranges: &[(usize, usize)]| {
+            let mut ranges = ranges.to_vec();
+            let n = super::merge_ranges(&mut ranges);
+            ranges.truncate(n);
+            ranges
+        };
+
+        assert_eq!(f(&[]), &[]);
+        assert_eq!(f(&[(10, 10), (0, 5)]), &[(0, 5), (10, 10)]); // unsorted
+        assert_eq!(f(&[(0, 10), (10, 10)]), &[(0, 20)]); // adjacent
+        assert_eq!(f(&[(0, 30), (10, 5), (20, 20)]), &[(0, 40)]); // contained
+        assert_eq!(
+            f(&[(0, 10), (5, 10), (20, 10), (40, 10), (45, 1)]),
+            &[(0, 15), (20, 10), (40, 10)]
+        );
+    }
+
+    #[test]
+    fn collect_regions() {
+        let regions = super::collect_regions(
+            &[(0x8000_0000, 0x1000_0000), (0x1000, 0x1000)], // unsorted
+            &[(0x8000_0000, 0x10_0000), (0x8f00_0000, 0x10_0000)],
+            &[(0x900_0000, 0x1000), (0x1000_0000, 0)], // empty range is skipped
+            (0x8020_0000, 0x20_0000),
+        );
+        let res: Vec<_> = regions
+            .iter()
+            .map(|r| (r.paddr.as_usize(), r.size, r.name))
+            .collect();
+        assert_eq!(
+            res,
+            &[
+                (0x1000, 0x1000, "free memory"),
+                (0x900_0000, 0x1000, "mmio"),
+                (0x8000_0000, 0x10_0000, "reserved"),
+                (0x8010_0000, 0x10_0000, "free memory"),
+                (0x8020_0000, 0x20_0000, "kernel image"),
+                (0x8040_0000, 0xec0_0000, "free memory"),
+                (0x8f00_0000, 0x10_0000, "reserved"),
+                (0x8f10_0000, 0xf0_0000, "free memory"),
+            ]
+        );
+        assert_eq!(regions[4].flags.bits(), DEFAULT_KERNEL_FLAGS.bits());
+        assert!(regions[1].flags.contains(MemRegionFlags::DEVICE));
+        assert!(regions[0].flags.contains(MemRegionFlags::FREE));
+    }
+
+    #[test]
+    fn collect_regions_overlapping_reserved() {
+        let regions = super::collect_regions(
+            &[(0x8000_0000, 0x100_0000)],
+            &[(0x8000_0000, 0x30_0000), (0x8010_0000, 0x10_0000)],
+            &[],
+            (0x8020_0000, 0x20_0000),
+        );
+        let free: Vec<_> = regions
+            .iter()
+            .filter(|r| r.flags.contains(MemRegionFlags::FREE))
+            .map(|r| (r.paddr.as_usize(), r.size))
+            .collect();
+        assert_eq!(free, &[(0x8040_0000, 0xc0_0000)]);
+    }
 }
@@ -1,5 +1,49 @@
 //! Power management.
 
+/// The kind of a CPU idle state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuIdleKind {
+    /// The core clock is gated (e.g., `wfi` or `hlt`), it can be woken up
+    /// almost immediately.
+    Standby,
+    /// The core logic is retained at a lower voltage, the wake-up latency is
+    /// higher than [`Standby`](Self::Standby).
+    Retention,
+    /// The core is powered down. The platform restores the CPU context on
+    /// wake-up, but local timers may stop in this state.
+    PowerDown,
+}
+
+/// A CPU idle state, returned by [`cpu_idle_states`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuIdleState {
+    /// The state name, used for identification.
+    pub name: &'static str,
+    /// The kind of the state.
+    pub kind: CpuIdleKind,
+    /// The worst-case latency in microseconds to enter the state.
+    pub entry_latency_us: u32,
+    /// The worst-case latency in microseconds to exit the state.
+    pub exit_latency_us: u32,
+}
+
+impl CpuIdleState {
+    /// Creates a new idle state.
+    pub const fn new(
+        name: &'static str,
+        kind: CpuIdleKind,
+        entry_latency_us: u32,
+        exit_latency_us: u32,
+    ) -> Self {
+        Self {
+            name,
+            kind,
+            entry_latency_us,
+            exit_latency_us,
+        }
+    }
+}
+
 /// Power management interface.
 #[def_plat_interface]
 pub trait PowerIf {
@@ -8,8 +52,35 @@ pub trait PowerIf {
     ///
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
+    ///
+    /// It can also be used to bring up a core again after it has been powered
+    /// off by [`cpu_off`], in which case the core starts from its secondary
+    /// entry as if it is booted for the first time.
     fn cpu_boot(cpu_id: usize, stack_top_paddr: usize);
 
+    /// Powers off the calling CPU core.
+    ///
+    /// It never returns. If the platform is not able to power off the core,
+    /// the core is parked with interrupts disabled.
+    fn cpu_off() -> !;
+
+    /// Returns all idle states supported by the calling CPU, ordered from the
+    /// shallowest to the deepest.
+    ///
+    /// There is at least one state (usually a [`Standby`](CpuIdleKind::Standby) one).
+    fn cpu_idle_states() -> &'static [CpuIdleState];
+
+    /// Puts the calling CPU into the idle state at `index` of
+    /// [`cpu_idle_states`], and returns after it is woken up (e.g., by an
+    /// interrupt).
+    ///
+    /// It must be called with local IRQs enabled, otherwise it may never
+    /// return. If `index` is out of range, the deepest state is used.
+    fn cpu_idle(index: usize);
+
     /// Shutdown the whole system.
     fn system_off() -> !;
+
+    /// Resets (reboots) the whole system.
+    fn system_reset() -> !;
 }
@@ -19,6 +19,127 @@ pub const NANOS_PER_MILLIS: u64 = 1_000_000;
 /// Number of nanoseconds in a microsecond.
 pub const NANOS_PER_MICROS: u64 = 1_000;
 
+/// A linear conversion `x * mult >> shift`, computed in 128 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MultShift {
+    mult: u64,
+    shift: u32,
+}
+
+impl MultShift {
+    /// Creates a conversion from `x` to `x * to / from`.
+    ///
+    /// The shift is chosen as large as possible while keeping `mult` in 64
+    /// bits, and `mult` is rounded up, so that exact integer results (e.g., a
+    /// whole second of ticks) are not truncated to the integer below.
+    const fn new(from: u64, to: u64) -> Self {
+        let mut shift = 0;
+        while shift < 64 && ((to as u128) << (shift + 1)).div_ceil(from as u128) <= u64::MAX as u128
+        {
+            shift += 1;
+        }
+        let mult = ((to as u128) << shift).div_ceil(from as u128);
+        assert!(mult <= u64::MAX as u128, "conversion ratio is too large");
+        Self {
+            mult: mult as u64,
+            shift,
+        }
+    }
+
+    const fn apply(self, x: u64) -> u64 {
+        let res = (x as u128 * self.mult as u128) >> self.shift;
+        if res > u64::MAX as u128 {
+            u64::MAX
+        } else {
+            res as u64
+        }
+    }
+}
+
+/// A free-running hardware counter, used to implement the tick conversions
+/// of [`TimeIf`].
+///
+/// Conversions are done by multiplying and shifting in 128 bits, so they do
+/// not overflow during the lifetime of the system, and keep sub-nanosecond
+/// precision for any counter frequency. Results that do not fit in 64 bits
+/// are saturated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSource {
+    freq_hz: u64,
+    mask: u64,
+    to_nanos: MultShift,
+    from_nanos: MultShift,
+}
+
+impl ClockSource {
+    /// Creates a clock source for a `bits`-bit counter running at `freq_hz`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `freq_hz` is 0 or `bits` is not in `1..=64`.
+    pub const fn new(freq_hz: u64, bits: u32) -> Self {
+        assert!(freq_hz > 0, "clock frequency must be non-zero");
+        assert!(bits > 0 && bits <= 64, "invalid counter width");
+        Self {
+            freq_hz,
+            mask: u64::MAX >> (64 - bits),
+            to_nanos: MultShift::new(freq_hz, NANOS_PER_SEC),
+            from_nanos: MultShift::new(NANOS_PER_SEC, freq_hz),
+        }
+    }
+
+    /// Creates a placeholder clock source that converts everything to 0.
+    ///
+    /// It can be used to initialize a static variable before the counter
+    /// frequency is known.
+    pub const fn zero() -> Self {
+        let zero = MultShift { mult: 0, shift: 0 };
+        Self {
+            freq_hz: 0,
+            mask: u64::MAX,
+            to_nanos: zero,
+            from_nanos: zero,
+        }
+    }
+
+    /// Returns the counter frequency in Hz.
+    pub const fn freq_hz(self) -> u64 {
+        self.freq_hz
+    }
+
+    /// Returns the mask of the valid counter bits.
+    pub const fn mask(self) -> u64 {
+        self.mask
+    }
+
+    /// Converts counter ticks to nanoseconds.
+    pub const fn ticks_to_nanos(self, ticks: u64) -> u64 {
+        self.to_nanos.apply(ticks)
+    }
+
+    /// Converts nanoseconds to counter ticks.
+    pub const fn nanos_to_ticks(self, nanos: u64) -> u64 {
+        self.from_nanos.apply(nanos)
+    }
+
+    /// Returns the ticks elapsed from counter value `last` to `now`, taking a
+    /// counter wrap into account.
+    pub const fn delta(self, now: u64, last: u64) -> u64 {
+        now.wrapping_sub(last) & self.mask
+    }
+
+    /// Returns the maximum nanoseconds that can be measured by [`delta`]
+    /// before the counter wraps, with a 12.5% safety margin.
+    ///
+    /// It is the longest time the system can stay idle without reading the
+    /// counter.
+    ///
+    /// [`delta`]: Self::delta
+    pub const fn max_idle_nanos(self) -> u64 {
+        self.ticks_to_nanos(self.mask - (self.mask >> 3))
+    }
+}
+
 /// Time-related interfaces.
 #[def_plat_interface]
 pub trait TimeIf {
@@ -73,3 +194,77 @@ pub fn busy_wait_until(deadline: TimeValue) {
         core::hint::spin_loop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ClockSource, NANOS_PER_SEC};
+
+    /// Returns `ticks * to / from` computed exactly.
+    fn exact(ticks: u64, from: u64, to: u64) -> u128 {
+        ticks as u128 * to as u128 / from as u128
+    }
+
+    #[test]
+    fn clock_source_odd_freqs() {
+        for freq in [
+            1,
+            3,
+            7,
+            32_768,
+            10_000_000,
+            24_000_000,
+            62_500_000,
+            1_193_182,
+            2_999_999_999,
+        ] {
+            let clock = ClockSource::new(freq, 64);
+            assert_eq!(clock.ticks_to_nanos(freq), NANOS_PER_SEC, "freq = {freq}");
+            assert_eq!(clock.nanos_to_ticks(NANOS_PER_SEC), freq, "freq = {freq}");
+            for ticks in [1, 12_345, freq / 3, freq * 7 + 1] {
+                let nanos = clock.ticks_to_nanos(ticks) as u128;
+                assert!(nanos.abs_diff(exact(ticks, freq, NANOS_PER_SEC)) <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn clock_source_long_uptime() {
+        const SECS_PER_YEAR: u64 = 365 * 24 * 3600;
+        // 3.123456789 GHz TSC, which overflowed `ticks * 1_000` in a few weeks
+        let freq = 3_123_456_789;
+        let clock = ClockSource::new(freq, 64);
+        let ticks = freq * SECS_PER_YEAR * 10;
+        let nanos = clock.ticks_to_nanos(ticks) as u128;
+        assert!(nanos.abs_diff(exact(ticks, freq, NANOS_PER_SEC)) <= 1);
+        assert_eq!(
+            clock.ticks_to_nanos(ticks),
+            NANOS_PER_SEC * SECS_PER_YEAR * 10
+        );
+
+        let nanos = NANOS_PER_SEC * SECS_PER_YEAR * 100;
+        let ticks = clock.nanos_to_ticks(nanos) as u128;
+        assert!(ticks.abs_diff(exact(nanos, NANOS_PER_SEC, freq)) <= 1);
+
+        // saturate instead of wrapping around
+        assert_eq!(ClockSource::new(1, 64).ticks_to_nanos(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn clock_source_wrap() {
+        let clock = ClockSource::new(14_318_180, 32);
+        assert_eq!(clock.mask(), 0xffff_ffff);
+        assert_eq!(clock.delta(0x10, 0xffff_fff0), 0x20);
+        assert_eq!(clock.delta(0x1_0000_0010, 0x10), 0);
+
+        // 2^32 ticks at 14.31818 MHz is about 300 seconds
+        let max_idle = clock.max_idle_nanos();
+        assert!((262 * NANOS_PER_SEC..263 * NANOS_PER_SEC).contains(&max_idle));
+    }
+
+    #[test]
+    fn clock_source_zero() {
+        let clock = ClockSource::zero();
+        assert_eq!(clock.ticks_to_nanos(12_345), 0);
+        assert_eq!(clock.nanos_to_ticks(12_345), 0);
+    }
+}
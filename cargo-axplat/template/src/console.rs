@@ -15,4 +15,10 @@ impl ConsoleIf for ConsoleIfImpl {
     fn read_bytes(bytes: &mut [u8]) -> usize {
         todo!()
     }
+
+    /// Returns the IRQ number of the console input, or `None` if the console
+    /// input is not interrupt-driven.
+    fn irq_num() -> Option<usize> {
+        todo!()
+    }
 }
@@ -1,4 +1,4 @@
-use axplat::irq::{IrqHandler, IrqIf};
+use axplat::irq::{IpiIf, IpiTarget, IrqHandler, IrqIf};
 
 struct IrqIfImpl;
 
@@ -34,3 +34,21 @@ impl IrqIf for IrqIfImpl {
         todo!()
     }
 }
+
+struct IpiIfImpl;
+
+#[impl_plat_interface]
+impl IpiIf for IpiIfImpl {
+    /// Returns the IRQ number of IPIs.
+    ///
+    /// The handler of IPIs should be registered with this IRQ number via
+    /// [`register`].
+    fn ipi_irq_num() -> usize {
+        todo!()
+    }
+
+    /// Sends an IPI to the given target CPU(s).
+    fn send_ipi(target: IpiTarget) {
+        todo!()
+    }
+}
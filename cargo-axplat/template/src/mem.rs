@@ -1,4 +1,5 @@
 use axplat::mem::{MemIf, RawRange};
+use memory_addr::{PhysAddr, VirtAddr};
 
 struct MemIfImpl;
 
@@ -27,4 +28,28 @@ impl MemIf for MemIfImpl {
     fn mmio_ranges() -> &'static [RawRange] {
         todo!()
     }
+
+    /// Translates a physical address to a virtual address.
+    ///
+    /// It is just an easy way to access physical memory when virtual memory
+    /// is enabled. The mapping may not be established yet.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        todo!()
+    }
+
+    /// Translates a virtual address to a physical address.
+    ///
+    /// It is a reverse operation of [`phys_to_virt`]. It requires that the
+    /// virtual address is in the linear mapping.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+        todo!()
+    }
+
+    /// Returns the offset between bus addresses and physical addresses.
+    ///
+    /// On some boards, devices (e.g., DMA masters) see the memory at a
+    /// different address from the CPU, where `bus_addr = paddr + offset`.
+    fn phys_bus_offset() -> usize {
+        todo!()
+    }
 }
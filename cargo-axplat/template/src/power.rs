@@ -1,4 +1,4 @@
-use axplat::power::PowerIf;
+use axplat::power::{CpuIdleState, PowerIf};
 
 struct PowerImpl;
 
@@ -9,12 +9,47 @@ impl PowerIf for PowerImpl {
     ///
     /// Where `cpu_id` is the logical CPU ID (0, 1, ..., N-1, N is the number of
     /// CPU cores on the platform).
+    ///
+    /// It can also be used to bring up a core again after it has been powered
+    /// off by [`cpu_off`], in which case the core starts from its secondary
+    /// entry as if it is booted for the first time.
     fn cpu_boot(cpu_id: usize, stack_top_paddr: usize) {
         todo!()
     }
 
+    /// Powers off the calling CPU core.
+    ///
+    /// It never returns. If the platform is not able to power off the core,
+    /// the core is parked with interrupts disabled.
+    fn cpu_off() -> ! {
+        todo!()
+    }
+
+    /// Returns all idle states supported by the calling CPU, ordered from the
+    /// shallowest to the deepest.
+    ///
+    /// There is at least one state (usually a [`Standby`](axplat::power::CpuIdleKind::Standby) one).
+    fn cpu_idle_states() -> &'static [CpuIdleState] {
+        todo!()
+    }
+
+    /// Puts the calling CPU into the idle state at `index` of
+    /// [`cpu_idle_states`], and returns after it is woken up (e.g., by an
+    /// interrupt).
+    ///
+    /// It must be called with local IRQs enabled, otherwise it may never
+    /// return. If `index` is out of range, the deepest state is used.
+    fn cpu_idle(index: usize) {
+        todo!()
+    }
+
     /// Shutdown the whole system.
     fn system_off() -> ! {
         todo!()
     }
+
+    /// Resets (reboots) the whole system.
+    fn system_reset() -> ! {
+        todo!()
+    }
 }
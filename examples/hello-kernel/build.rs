@@ -10,9 +10,22 @@ fn kernel_base(arch: &str) -> usize {
     }
 }
 
+/// The offset between the virtual and load addresses of the kernel image.
+///
+/// x86_64 kernels can be loaded by their ELF program headers (e.g., PVH boot),
+/// so the load addresses must be physical addresses.
+fn load_offset(arch: &str) -> usize {
+    match arch {
+        "x86_64" => 0xffff_8000_0000_0000,
+        _ => 0,
+    }
+}
+
 fn gen_linker_script(arch: &str) -> Result<()> {
     let ld_content = std::fs::read_to_string("linker.lds.S")?;
-    let ld_content = ld_content.replace("%KERNEL_BASE%", &format!("{:#x}", kernel_base(arch)));
+    let ld_content = ld_content
+        .replace("%KERNEL_BASE%", &format!("{:#x}", kernel_base(arch)))
+        .replace("%LOAD_OFFSET%", &format!("{:#x}", load_offset(arch)));
     let root = std::env::var("CARGO_MANIFEST_DIR").unwrap();
     let out_fname = format!("linker_{arch}.lds");
     std::fs::write(&out_fname, ld_content)?;
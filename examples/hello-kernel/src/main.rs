@@ -15,14 +15,30 @@ cfg_if::cfg_if! {
     }
 }
 
+/// The CPU ID of the only CPU running this example.
+#[cfg(target_arch = "riscv64")]
+static BOOT_CPU_ID: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
 fn init_kernel(cpu_id: usize, arg: usize) {
     // x86_64 requires the `percpu` crate to be initialized first
     #[cfg(target_arch = "x86_64")]
     axcpu::init::init_percpu(cpu_id);
 
+    // Other platforms read the CPU ID from the hardware, while riscv64 needs
+    // it from the per-CPU data of the kernel.
+    #[cfg(target_arch = "riscv64")]
+    {
+        use core::sync::atomic::Ordering;
+        BOOT_CPU_ID.store(cpu_id, Ordering::Relaxed);
+        axplat::console::set_cpu_id_fn(|| BOOT_CPU_ID.load(Ordering::Relaxed));
+    }
+
     // Initialize trap, console, time.
     axplat::init::init_early(cpu_id, arg);
 
+    // Print platform logs to the console.
+    axplat::console::init_logger(log::LevelFilter::Info).ok();
+
     // Initialize platform devices (not used in this example).
     axplat::init::init_later(cpu_id, arg);
 }
@@ -1,6 +1,6 @@
 //! snps,dw-apb-uart serial driver
 
-use axplat::console::ConsoleIf;
+use axplat::console::{ConsoleIf, ConsoleRx};
 
 use crate::mem::phys_to_virt;
 use dw_apb_uart::DW8250;
@@ -11,6 +11,8 @@ const UART_BASE: PhysAddr = pa!(crate::config::devices::UART_PADDR);
 
 static UART: SpinNoIrq<DW8250> = SpinNoIrq::new(DW8250::new(phys_to_virt(UART_BASE).as_usize()));
 
+static RX: ConsoleRx<256> = ConsoleRx::new();
+
 /// Writes a byte to the console.
 #[allow(dead_code)]
 pub fn putchar(c: u8) {
@@ -24,27 +26,25 @@ pub fn putchar(c: u8) {
     }
 }
 
-/// Reads a byte from the console, or returns [`None`] if no input is available.
-fn getchar() -> Option<u8> {
-    UART.lock().getchar()
-}
-
 /// UART simply initialize
 pub fn init_early() {
     UART.lock().init();
 }
 
-/// Set UART IRQ Enable
+/// Registers the UART IRQ handler, and enables the UART IRQ if it succeeds.
 #[cfg(feature = "irq")]
 pub fn init_irq() {
-    UART.lock().set_ier(true);
-    axplat_aarch64_common::gic::register_handler(crate::config::devices::UART_IRQ, handle);
+    if RX.register_irq(crate::config::devices::UART_IRQ, handle) {
+        UART.lock().set_ier(true);
+    } else {
+        warn!("failed to register the UART IRQ");
+    }
 }
 
-/// UART IRQ Handler
-#[allow(dead_code)]
-pub fn handle() {
-    trace!("Uart IRQ Handler");
+#[cfg(feature = "irq")]
+fn handle() {
+    let mut uart = UART.lock();
+    RX.receive(|| uart.getchar());
 }
 
 struct ConsoleIfImpl;
@@ -61,15 +61,13 @@ impl ConsoleIf for ConsoleIfImpl {
     /// Reads bytes from the console into the given mutable slice.
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize {
-        let mut read_len = 0;
-        while read_len < bytes.len() {
-            if let Some(c) = getchar() {
-                bytes[read_len] = c;
-            } else {
-                break;
-            }
-            read_len += 1;
-        }
-        read_len
+        let mut uart = UART.lock();
+        RX.read(bytes, || uart.getchar())
+    }
+
+    /// Returns the IRQ number of the console input, or `None` if the console
+    /// input is not interrupt-driven.
+    fn irq_num() -> Option<usize> {
+        RX.irq_num()
     }
 }
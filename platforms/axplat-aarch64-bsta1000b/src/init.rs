@@ -2,7 +2,7 @@ use axplat::init::InitIf;
 
 #[allow(unused_imports)]
 use crate::config::devices::{GICC_PADDR, GICD_PADDR, TIMER_IRQ};
-use crate::config::plat::PSCI_METHOD;
+use crate::config::plat::{PSCI_METHOD, PSCI_RETENTION_ENTRY_LATENCY, PSCI_RETENTION_EXIT_LATENCY};
 
 struct InitIfImpl;
 
@@ -15,7 +15,12 @@ impl InitIf for InitIfImpl {
     /// early console, clocking).
     fn init_early(_cpu_id: usize, _dtb: usize) {
         axcpu::init::init_trap();
+        axplat::console::set_cpu_id_fn(axplat_aarch64_common::current_cpu_hw_id);
         axplat_aarch64_common::psci::init(PSCI_METHOD);
+        axplat_aarch64_common::psci::init_idle_states(
+            PSCI_RETENTION_ENTRY_LATENCY as u32,
+            PSCI_RETENTION_EXIT_LATENCY as u32,
+        );
         super::dw_apb_uart::init_early();
         axplat_aarch64_common::generic_timer::init_early();
     }
@@ -31,6 +36,12 @@ impl InitIf for InitIfImpl {
     /// initialization (e.g, logging, memory management), and finalized the rest of
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _dtb: usize) {
+        #[cfg(feature = "klog")]
+        {
+            use crate::config::plat::{KLOG_PADDR, KLOG_SIZE};
+            // SAFETY: the range is reserved for the kernel log buffer only.
+            unsafe { axplat::console::klog::set_reserved_buffer((KLOG_PADDR, KLOG_SIZE)) };
+        }
         #[cfg(feature = "irq")]
         {
             use crate::mem::phys_to_virt;
@@ -35,7 +35,6 @@ pub fn reset_cpu() {
 }
 
 /// reboot system
-#[allow(dead_code)]
 pub fn do_reset() {
     // wait 50 ms
     busy_wait(Duration::from_millis(50));
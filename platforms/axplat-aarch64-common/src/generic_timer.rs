@@ -2,10 +2,9 @@
 
 use aarch64_cpu::registers::{CNTFRQ_EL0, CNTP_CTL_EL0, CNTP_TVAL_EL0, CNTPCT_EL0};
 use aarch64_cpu::registers::{Readable, Writeable};
-use int_ratio::Ratio;
+use axplat::time::ClockSource;
 
-static mut CNTPCT_TO_NANOS_RATIO: Ratio = Ratio::zero();
-static mut NANOS_TO_CNTPCT_RATIO: Ratio = Ratio::zero();
+static mut CLOCK: ClockSource = ClockSource::zero();
 
 /// Returns the current clock time in hardware ticks.
 #[inline]
@@ -16,13 +15,13 @@ pub fn current_ticks() -> u64 {
 /// Converts hardware ticks to nanoseconds.
 #[inline]
 pub fn ticks_to_nanos(ticks: u64) -> u64 {
-    unsafe { CNTPCT_TO_NANOS_RATIO.mul_trunc(ticks) }
+    unsafe { CLOCK.ticks_to_nanos(ticks) }
 }
 
 /// Converts nanoseconds to hardware ticks.
 #[inline]
 pub fn nanos_to_ticks(nanos: u64) -> u64 {
-    unsafe { NANOS_TO_CNTPCT_RATIO.mul_trunc(nanos) }
+    unsafe { CLOCK.nanos_to_ticks(nanos) }
 }
 
 /// Set a one-shot timer.
@@ -43,10 +42,7 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
 /// Early stage initialization: stores the timer frequency.
 pub fn init_early() {
     let freq = CNTFRQ_EL0.get();
-    unsafe {
-        CNTPCT_TO_NANOS_RATIO = Ratio::new(axplat::time::NANOS_PER_SEC as u32, freq as u32);
-        NANOS_TO_CNTPCT_RATIO = CNTPCT_TO_NANOS_RATIO.inverse();
-    }
+    unsafe { CLOCK = ClockSource::new(freq, 64) };
 }
 
 /// Enable timer interrupts.
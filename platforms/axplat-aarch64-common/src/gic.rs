@@ -1,7 +1,7 @@
 //! ARM Generic Interrupt Controller (GIC).
 
 use arm_gicv2::{GicCpuInterface, GicDistributor};
-use axplat::irq::{HandlerTable, IrqHandler};
+use axplat::irq::{HandlerTable, IpiTarget, IrqHandler};
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use memory_addr::VirtAddr;
@@ -9,7 +9,17 @@ use memory_addr::VirtAddr;
 /// The maximum number of IRQs.
 const MAX_IRQ_COUNT: usize = 1024;
 
+/// The software generated interrupt (SGI) used for IPIs.
+pub const IPI_IRQ_NUM: usize = 1;
+
+/// Offset of the Software Generated Interrupt Register (GICD_SGIR).
+const GICD_SGIR_OFFSET: usize = 0xf00;
+
+/// The number of CPU interfaces that an SGI can target in GICv2.
+const MAX_SGI_TARGETS: usize = 8;
+
 static GICD: LazyInit<SpinNoIrq<GicDistributor>> = LazyInit::new();
+static GICD_BASE: LazyInit<VirtAddr> = LazyInit::new();
 
 // per-CPU, no lock
 static GICC: LazyInit<GicCpuInterface> = LazyInit::new();
@@ -60,9 +70,40 @@ pub fn handle_irq(_unused: usize) {
     });
 }
 
+/// Sends an IPI to the given target CPU(s) by generating SGI
+/// [`IPI_IRQ_NUM`].
+///
+/// The logical CPU ID is used as the GIC CPU interface number, which is the
+/// bit index in the 8-bit CPUTargetList of GICv2. An IPI to a CPU with ID 8 or
+/// above is dropped with a warning.
+pub fn send_ipi(target: IpiTarget) {
+    // TargetListFilter: 0 = use CPUTargetList, 1 = all but self, 2 = self only
+    let sgir = match target {
+        IpiTarget::Cpu { cpu_id } if cpu_id < MAX_SGI_TARGETS => 1u32 << (16 + cpu_id),
+        IpiTarget::Cpu { cpu_id } => {
+            warn!("CPU {cpu_id} has no GICv2 CPU interface number, IPI dropped");
+            return;
+        }
+        IpiTarget::AllExceptCurrent { .. } => 1 << 24,
+        IpiTarget::All { .. } => {
+            write_sgir(1 << 24);
+            2 << 24
+        }
+    };
+    write_sgir(sgir);
+}
+
+fn write_sgir(filter: u32) {
+    trace!("GICD SGIR: {:#x}", filter);
+    let sgir = (*GICD_BASE + GICD_SGIR_OFFSET).as_mut_ptr_of::<u32>();
+    // SAFETY: `GICD_BASE` is a valid MMIO address of the GIC distributor.
+    unsafe { sgir.write_volatile(filter | IPI_IRQ_NUM as u32) };
+}
+
 /// Initializes GICD (for the primary CPU only).
 pub fn init_gicd(gicd_base: VirtAddr, gicc_base: VirtAddr) {
     info!("Initialize GICv2...");
+    GICD_BASE.init_once(gicd_base);
     GICD.init_once(SpinNoIrq::new(GicDistributor::new(gicd_base.as_mut_ptr())));
     GICC.init_once(GicCpuInterface::new(gicc_base.as_mut_ptr()));
     GICD.lock().init();
@@ -115,3 +156,24 @@ macro_rules! irq_if_impl {
         }
     };
 }
+
+/// Default implementation of [`axplat::irq::IpiIf`] using the GIC.
+#[macro_export]
+macro_rules! ipi_if_impl {
+    ($name:ident) => {
+        struct $name;
+
+        #[impl_plat_interface]
+        impl axplat::irq::IpiIf for $name {
+            /// Returns the IRQ number of IPIs.
+            fn ipi_irq_num() -> usize {
+                $crate::gic::IPI_IRQ_NUM
+            }
+
+            /// Sends an IPI to the given target CPU(s).
+            fn send_ipi(target: axplat::irq::IpiTarget) {
+                $crate::gic::send_ipi(target)
+            }
+        }
+    };
+}
@@ -18,3 +18,11 @@ pub mod gic;
 pub mod pl011;
 pub mod pl031;
 pub mod psci;
+
+use aarch64_cpu::registers::{MPIDR_EL1, Readable};
+
+/// Returns the hardware ID of the current CPU, i.e., the affinity fields
+/// `Aff2..Aff0` of `MPIDR_EL1`.
+pub fn current_cpu_hw_id() -> usize {
+    MPIDR_EL1.get() as usize & 0xff_ffff
+}
@@ -1,12 +1,15 @@
 //! PL011 UART.
 
 use arm_pl011::Pl011Uart;
+use axplat::console::ConsoleRx;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use memory_addr::VirtAddr;
 
 static UART: LazyInit<SpinNoIrq<Pl011Uart>> = LazyInit::new();
 
+static RX: ConsoleRx<256> = ConsoleRx::new();
+
 fn do_putchar(uart: &mut Pl011Uart, c: u8) {
     match c {
         b'\n' => {
@@ -37,17 +40,18 @@ pub fn write_bytes(bytes: &[u8]) {
 
 /// Reads bytes from the console into the given mutable slice.
 /// Returns the number of bytes read.
+///
+/// Bytes buffered by [`irq_handler`] are returned first, followed by the
+/// ones still in the UART FIFO.
 pub fn read_bytes(bytes: &mut [u8]) -> usize {
-    let mut read_len = 0;
-    while read_len < bytes.len() {
-        if let Some(c) = getchar() {
-            bytes[read_len] = c;
-        } else {
-            break;
-        }
-        read_len += 1;
-    }
-    read_len
+    let mut uart = UART.lock();
+    RX.read(bytes, || uart.getchar())
+}
+
+/// Returns the UART IRQ number if the receive interrupt is enabled by
+/// [`init_irq`].
+pub fn irq_num() -> Option<usize> {
+    RX.irq_num()
 }
 
 /// Early stage initialization of the PL011 UART driver.
@@ -56,14 +60,22 @@ pub fn init_early(uart_base: VirtAddr) {
     UART.lock().init();
 }
 
+/// Registers [`irq_handler`] for the given UART IRQ to enable the
+/// interrupt-driven console input.
+pub fn init_irq(irq_num: usize) {
+    RX.register_irq(irq_num, irq_handler);
+}
+
 /// UART IRQ Handler
+///
+/// It acknowledges the PL011 interrupts, and buffers the received bytes for
+/// [`read_bytes`].
 pub fn irq_handler() {
-    let is_receive_interrupt = UART.lock().is_receive_interrupt();
-    UART.lock().ack_interrupts();
+    let mut uart = UART.lock();
+    let is_receive_interrupt = uart.is_receive_interrupt();
+    uart.ack_interrupts();
     if is_receive_interrupt {
-        while let Some(c) = getchar() {
-            putchar(c);
-        }
+        RX.receive(|| uart.getchar());
     }
 }
 
@@ -87,6 +99,12 @@ macro_rules! console_if_impl {
             fn read_bytes(bytes: &mut [u8]) -> usize {
                 $crate::pl011::read_bytes(bytes)
             }
+
+            /// Returns the IRQ number of the console input, or `None` if the
+            /// console input is not interrupt-driven.
+            fn irq_num() -> Option<usize> {
+                $crate::pl011::irq_num()
+            }
         }
     };
 }
@@ -4,6 +4,9 @@
 
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use axplat::power::{CpuIdleKind, CpuIdleState};
+use lazyinit::LazyInit;
+
 const PSCI_0_2_FN_BASE: u32 = 0x84000000;
 const PSCI_0_2_64BIT: u32 = 0x40000000;
 const PSCI_0_2_FN_CPU_SUSPEND: u32 = PSCI_0_2_FN_BASE + 1;
@@ -12,10 +15,24 @@ const PSCI_0_2_FN_CPU_ON: u32 = PSCI_0_2_FN_BASE + 3;
 const PSCI_0_2_FN_MIGRATE: u32 = PSCI_0_2_FN_BASE + 5;
 const PSCI_0_2_FN_SYSTEM_OFF: u32 = PSCI_0_2_FN_BASE + 8;
 const PSCI_0_2_FN_SYSTEM_RESET: u32 = PSCI_0_2_FN_BASE + 9;
+const PSCI_1_0_FN_PSCI_FEATURES: u32 = PSCI_0_2_FN_BASE + 0xa;
 const PSCI_0_2_FN64_CPU_SUSPEND: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 1;
 const PSCI_0_2_FN64_CPU_ON: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 3;
 const PSCI_0_2_FN64_MIGRATE: u32 = PSCI_0_2_FN_BASE + PSCI_0_2_64BIT + 5;
 
+const PSCI_0_2_POWER_STATE_TYPE_SHIFT: u32 = 16;
+const PSCI_POWER_STATE_TYPE_STANDBY: u32 = 0;
+
+/// Set in the `CPU_SUSPEND` feature flags if the power state parameter uses
+/// the extended StateID format.
+const PSCI_1_0_FEATURES_CPU_SUSPEND_PF_EXTENDED: usize = 1 << 1;
+
+/// The idle state entered by a plain `wfi`, which is always available.
+const WFI_STATE: CpuIdleState = CpuIdleState::new("wfi", CpuIdleKind::Standby, 1, 1);
+
+/// CPU idle states supported through PSCI, set by [`init_idle_states`].
+static IDLE_STATES: LazyInit<[CpuIdleState; 2]> = LazyInit::new();
+
 static PSCI_METHOD_HVC: AtomicBool = AtomicBool::new(false);
 
 /// PSCI return values, inclusive of all PSCI versions.
@@ -82,12 +99,16 @@ fn psci_hvc_call(func: u32, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
-fn psci_call(func: u32, arg0: usize, arg1: usize, arg2: usize) -> Result<(), PsciError> {
-    let ret = if PSCI_METHOD_HVC.load(Ordering::Acquire) {
+fn psci_call_raw(func: u32, arg0: usize, arg1: usize, arg2: usize) -> usize {
+    if PSCI_METHOD_HVC.load(Ordering::Acquire) {
         psci_hvc_call(func, arg0, arg1, arg2)
     } else {
         arm_smccc_smc(func, arg0, arg1, arg2)
-    };
+    }
+}
+
+fn psci_call(func: u32, arg0: usize, arg1: usize, arg2: usize) -> Result<(), PsciError> {
+    let ret = psci_call_raw(func, arg0, arg1, arg2);
     if ret == 0 {
         Ok(())
     } else {
@@ -95,6 +116,20 @@ fn psci_call(func: u32, arg0: usize, arg1: usize, arg2: usize) -> Result<(), Psc
     }
 }
 
+/// Queries whether the given PSCI function is implemented, and returns its
+/// feature flags if so.
+///
+/// `PSCI_FEATURES` is introduced in PSCI v1.0. Older firmware reports it as
+/// not supported, like any other unknown function.
+fn psci_features(func: u32) -> Result<usize, PsciError> {
+    let ret = psci_call_raw(PSCI_1_0_FN_PSCI_FEATURES, func as usize, 0, 0);
+    if (ret as i32) < 0 {
+        Err(PsciError::from(ret as i32))
+    } else {
+        Ok(ret)
+    }
+}
+
 /// Initialize with the given PSCI method.
 ///
 /// Method should be either "smc" or "hvc".
@@ -106,6 +141,48 @@ pub fn init(method: &str) {
     }
 }
 
+/// Probes the idle states supported by the firmware.
+///
+/// The retention state, entered by `CPU_SUSPEND` with a standby power state,
+/// is advertised only if `PSCI_FEATURES` reports `CPU_SUSPEND` with the
+/// original power state format. Its entry and exit latencies (in
+/// microseconds) are given by the platform configuration, since PSCI does not
+/// report them.
+///
+/// It must be called after [`init`].
+pub fn init_idle_states(retention_entry_latency_us: u32, retention_exit_latency_us: u32) {
+    match psci_features(PSCI_0_2_FN64_CPU_SUSPEND) {
+        Ok(flags) if flags & PSCI_1_0_FEATURES_CPU_SUSPEND_PF_EXTENDED == 0 => {
+            IDLE_STATES.init_once([
+                WFI_STATE,
+                CpuIdleState::new(
+                    "cpu-retention",
+                    CpuIdleKind::Retention,
+                    retention_entry_latency_us,
+                    retention_exit_latency_us,
+                ),
+            ]);
+        }
+        Ok(_) => info!("PSCI CPU_SUSPEND uses the extended power state format, retention disabled"),
+        Err(e) => info!(
+            "PSCI CPU_SUSPEND is not available ({:?}), retention disabled",
+            e
+        ),
+    }
+}
+
+/// Returns the CPU idle states supported through PSCI.
+///
+/// Only the `wfi` state is returned if the retention state is not
+/// supported, or before [`init_idle_states`] is called.
+pub fn idle_states() -> &'static [CpuIdleState] {
+    if IDLE_STATES.is_inited() {
+        IDLE_STATES.as_slice()
+    } else {
+        &[WFI_STATE]
+    }
+}
+
 /// Shutdown the whole system, including all CPUs.
 pub fn system_off() -> ! {
     info!("Shutting down...");
@@ -116,6 +193,16 @@ pub fn system_off() -> ! {
     }
 }
 
+/// Reset the whole system, including all CPUs.
+pub fn system_reset() -> ! {
+    info!("Resetting...");
+    psci_call(PSCI_0_2_FN_SYSTEM_RESET, 0, 0, 0).ok();
+    warn!("It should reset!");
+    loop {
+        axcpu::asm::halt();
+    }
+}
+
 /// Power up a core. This call is used to power up cores that either:
 ///
 /// * Have not yet been booted into the calling supervisory software.
@@ -132,13 +219,38 @@ pub fn cpu_on(target_cpu: usize, entry_point: usize, arg: usize) {
     }
 }
 
+/// Suspend the calling core into a standby (retention) power state. It
+/// returns when the core is woken up by an interrupt.
+pub fn cpu_suspend_standby() {
+    let state = PSCI_POWER_STATE_TYPE_STANDBY << PSCI_0_2_POWER_STATE_TYPE_SHIFT;
+    if let Err(e) = psci_call(PSCI_0_2_FN64_CPU_SUSPEND, state as usize, 0, 0) {
+        // fall back to a plain `wfi`
+        debug!("failed to suspend CPU ({:?})", e);
+        axcpu::asm::wait_for_irqs();
+    }
+}
+
+/// Enters the idle state at `index` of [`idle_states`].
+///
+/// If `index` is out of range, the deepest state is used.
+pub fn cpu_idle(index: usize) {
+    match index.min(idle_states().len() - 1) {
+        0 => axcpu::asm::wait_for_irqs(),
+        _ => cpu_suspend_standby(),
+    }
+}
+
 /// Power down the calling core. This call is intended for use in hotplug. A
 /// core that is powered down by `cpu_off` can only be powered up again in
 /// response to a `cpu_on`.
-pub fn cpu_off() {
-    const PSCI_POWER_STATE_TYPE_STANDBY: u32 = 0;
-    const PSCI_POWER_STATE_TYPE_POWER_DOWN: u32 = 1;
-    const PSCI_0_2_POWER_STATE_TYPE_SHIFT: u32 = 16;
-    let state: u32 = PSCI_POWER_STATE_TYPE_POWER_DOWN << PSCI_0_2_POWER_STATE_TYPE_SHIFT;
-    psci_call(PSCI_0_2_FN_CPU_OFF, state as usize, 0, 0).ok();
+///
+/// If the call fails, the core is parked with interrupts disabled.
+pub fn cpu_off() -> ! {
+    axcpu::asm::disable_irqs();
+    if let Err(e) = psci_call(PSCI_0_2_FN_CPU_OFF, 0, 0, 0) {
+        error!("failed to power off CPU ({:?})", e);
+    }
+    loop {
+        axcpu::asm::halt();
+    }
 }
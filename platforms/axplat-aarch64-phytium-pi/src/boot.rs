@@ -38,7 +38,8 @@ unsafe fn enable_fp() {
     axcpu::asm::enable_fp();
 }
 
-fn hart_to_logid(hard_id: usize) -> usize {
+/// Maps the hardware ID of a CPU to its logical CPU ID.
+pub(crate) fn hart_to_logid(hard_id: usize) -> usize {
     crate::config::plat::CPU_ID_LIST
         .iter()
         .position(|&x| x == hard_id)
@@ -26,3 +26,6 @@ axplat_aarch64_common::time_if_impl!(TimeIfImpl);
 
 #[cfg(feature = "irq")]
 axplat_aarch64_common::irq_if_impl!(IrqIfImpl);
+
+#[cfg(feature = "irq")]
+axplat_aarch64_common::ipi_if_impl!(IpiIfImpl);
@@ -2,7 +2,17 @@ use axplat::mem::{MemIf, RawRange};
 use memory_addr::{PhysAddr, VirtAddr};
 
 use crate::config::devices::MMIO_RANGES;
-use crate::config::plat::{PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use crate::config::plat::{
+    KLOG_PADDR, KLOG_SIZE, PHYS_BUS_OFFSET, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET,
+};
+
+/// Reserved physical memory ranges: the range of the kernel log buffer if the
+/// `klog` feature is enabled and the range is configured.
+const RESERVED_RANGES: &[RawRange] = if cfg!(feature = "klog") && KLOG_SIZE > 0 {
+    &[(KLOG_PADDR, KLOG_SIZE)]
+} else {
+    &[]
+};
 
 struct MemIfImpl;
 
@@ -10,7 +20,6 @@ pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
 }
 
-#[allow(dead_code)]
 pub const fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
     pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
 }
@@ -33,11 +42,26 @@ impl MemIf for MemIfImpl {
     /// Note that the ranges returned should not include the range where the
     /// kernel is loaded.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[]
+        RESERVED_RANGES
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange] {
         &MMIO_RANGES
     }
+
+    /// Translates a physical address to a virtual address.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        phys_to_virt(paddr)
+    }
+
+    /// Translates a virtual address to a physical address.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+        virt_to_phys(vaddr)
+    }
+
+    /// Returns the offset between bus addresses and physical addresses.
+    fn phys_bus_offset() -> usize {
+        PHYS_BUS_OFFSET
+    }
 }
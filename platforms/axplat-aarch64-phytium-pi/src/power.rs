@@ -1,4 +1,4 @@
-use axplat::power::PowerIf;
+use axplat::power::{CpuIdleState, PowerIf};
 
 struct PowerImpl;
 
@@ -24,9 +24,29 @@ impl PowerIf for PowerImpl {
         }
     }
 
+    /// Powers off the calling CPU core.
+    fn cpu_off() -> ! {
+        axplat_aarch64_common::psci::cpu_off()
+    }
+
+    /// Returns all idle states supported by the calling CPU.
+    fn cpu_idle_states() -> &'static [CpuIdleState] {
+        axplat_aarch64_common::psci::idle_states()
+    }
+
+    /// Puts the calling CPU into the idle state at `index`.
+    fn cpu_idle(index: usize) {
+        axplat_aarch64_common::psci::cpu_idle(index)
+    }
+
     /// Shutdown the whole system.
     fn system_off() -> ! {
         info!("Shutting down...");
         axplat_aarch64_common::psci::system_off()
     }
+
+    /// Resets (reboots) the whole system.
+    fn system_reset() -> ! {
+        axplat_aarch64_common::psci::system_reset()
+    }
 }
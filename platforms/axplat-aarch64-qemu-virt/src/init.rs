@@ -2,7 +2,7 @@ use axplat::init::InitIf;
 
 #[allow(unused_imports)]
 use crate::config::devices::{GICC_PADDR, GICD_PADDR, RTC_PADDR, TIMER_IRQ, UART_IRQ, UART_PADDR};
-use crate::config::plat::PSCI_METHOD;
+use crate::config::plat::{PSCI_METHOD, PSCI_RETENTION_ENTRY_LATENCY, PSCI_RETENTION_EXIT_LATENCY};
 use crate::mem::phys_to_virt;
 
 struct InitIfImpl;
@@ -14,13 +14,19 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _dtb: usize) {
+    fn init_early(_cpu_id: usize, dtb: usize) {
         axcpu::init::init_trap();
+        axplat::console::set_cpu_id_fn(axplat_aarch64_common::current_cpu_hw_id);
         axplat_aarch64_common::pl011::init_early(phys_to_virt(pa!(UART_PADDR)));
         axplat_aarch64_common::psci::init(PSCI_METHOD);
+        axplat_aarch64_common::psci::init_idle_states(
+            PSCI_RETENTION_ENTRY_LATENCY as u32,
+            PSCI_RETENTION_EXIT_LATENCY as u32,
+        );
         axplat_aarch64_common::generic_timer::init_early();
         #[cfg(feature = "rtc")]
         axplat_aarch64_common::pl031::init_early(phys_to_virt(pa!(RTC_PADDR)));
+        crate::mem::init(dtb);
     }
 
     /// Initializes the platform at the early stage for secondary cores.
@@ -34,6 +40,12 @@ impl InitIf for InitIfImpl {
     /// initialization (e.g, logging, memory management), and finalized the rest of
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _dtb: usize) {
+        #[cfg(feature = "klog")]
+        {
+            use crate::config::plat::{KLOG_PADDR, KLOG_SIZE};
+            // SAFETY: the range is reserved for the kernel log buffer only.
+            unsafe { axplat::console::klog::set_reserved_buffer((KLOG_PADDR, KLOG_SIZE)) };
+        }
         #[cfg(feature = "irq")]
         {
             use crate::mem::phys_to_virt;
@@ -45,7 +57,7 @@ impl InitIf for InitIfImpl {
             axplat_aarch64_common::generic_timer::enable_irqs(TIMER_IRQ);
 
             // enable UART IRQs
-            axplat::irq::register(UART_IRQ, axplat_aarch64_common::pl011::irq_handler);
+            axplat_aarch64_common::pl011::init_irq(UART_IRQ);
         }
     }
 
@@ -1,8 +1,20 @@
-use axplat::mem::{MemIf, RawRange};
+use axplat::fdt::Fdt;
+use axplat::mem::{MemIf, RawRange, normalize_ranges, ranges_contain};
+use heapless::Vec;
+use lazyinit::LazyInit;
 use memory_addr::{PhysAddr, VirtAddr};
 
 use crate::config::devices::MMIO_RANGES;
-use crate::config::plat::{PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use crate::config::plat::{
+    KERNEL_BASE_PADDR, KERNEL_BASE_VADDR, KLOG_PADDR, KLOG_SIZE, PHYS_BUS_OFFSET, PHYS_MEMORY_BASE,
+    PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET,
+};
+
+/// The maximum number of RAM or reserved regions.
+const MAX_REGIONS: usize = 16;
+
+static RAM_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
+static RESERVED_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
 
 struct MemIfImpl;
 
@@ -10,11 +22,67 @@ pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
 }
 
-#[allow(dead_code)]
 pub const fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
     pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
 }
 
+/// Returns the physical memory range where the kernel image is loaded.
+fn kernel_image_range() -> RawRange {
+    unsafe extern "C" {
+        fn _ekernel();
+    }
+    (KERNEL_BASE_PADDR, _ekernel as usize - KERNEL_BASE_VADDR)
+}
+
+/// Parses the device tree blob to get the physical memory layout.
+///
+/// If the blob is invalid, `PHYS_MEMORY_BASE` and `PHYS_MEMORY_SIZE` in the
+/// configuration are used instead.
+pub(crate) fn init(dtb_paddr: usize) {
+    let mut ram = Vec::new();
+    let mut reserved: Vec<RawRange, MAX_REGIONS> = Vec::new();
+
+    let fdt = match dtb_paddr {
+        0 => None,
+        _ => unsafe { Fdt::from_ptr(phys_to_virt(pa!(dtb_paddr)).as_ptr()) }
+            .inspect_err(|e| log::warn!("Invalid DTB at {dtb_paddr:#x}: {e}"))
+            .ok(),
+    };
+    if let Some(fdt) = fdt {
+        for r in fdt.memory_regions().filter(|r| r.1 > 0) {
+            if ram.push(r).is_err() {
+                log::warn!("Too many RAM regions in DTB, ignore {r:#x?}");
+            }
+        }
+        for r in fdt.reserved_regions_with_blob(dtb_paddr) {
+            if reserved.push(r).is_err() {
+                log::warn!("Too many reserved regions in DTB, ignore {r:#x?}");
+            }
+        }
+    }
+
+    if ram.is_empty() {
+        ram.push((PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE)).unwrap();
+    }
+    ram.sort_unstable_by_key(|r| r.0);
+    if cfg!(feature = "klog") && KLOG_SIZE > 0 {
+        let klog = (KLOG_PADDR, KLOG_SIZE);
+        if !ranges_contain(&ram, klog) {
+            log::warn!("klog range {klog:#x?} is not in RAM, ignore it");
+        } else if reserved.push(klog).is_err() {
+            log::warn!("Too many reserved regions, ignore klog range {klog:#x?}");
+        }
+    }
+    RAM_REGIONS.init_once(ram);
+    let mut res = Vec::new();
+    normalize_ranges(&mut reserved, &[kernel_image_range()], |r| {
+        if res.push(r).is_err() {
+            log::warn!("Too many reserved regions, ignore {r:#x?}");
+        }
+    });
+    RESERVED_REGIONS.init_once(res);
+}
+
 #[impl_plat_interface]
 impl MemIf for MemIfImpl {
     /// Returns all physical memory (RAM) ranges on the platform.
@@ -22,7 +90,7 @@ impl MemIf for MemIfImpl {
     /// All memory ranges except reserved ranges (including the kernel loaded
     /// range) are free for allocation.
     fn phys_ram_ranges() -> &'static [RawRange] {
-        &[(PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE)]
+        RAM_REGIONS.as_slice()
     }
 
     /// Returns all reserved physical memory ranges on the platform.
@@ -33,11 +101,26 @@ impl MemIf for MemIfImpl {
     /// Note that the ranges returned should not include the range where the
     /// kernel is loaded.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[]
+        RESERVED_REGIONS.as_slice()
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange] {
         &MMIO_RANGES
     }
+
+    /// Translates a physical address to a virtual address.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        phys_to_virt(paddr)
+    }
+
+    /// Translates a virtual address to a physical address.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+        virt_to_phys(vaddr)
+    }
+
+    /// Returns the offset between bus addresses and physical addresses.
+    fn phys_bus_offset() -> usize {
+        PHYS_BUS_OFFSET
+    }
 }
@@ -16,6 +16,7 @@ impl InitIf for InitIfImpl {
     /// early console, clocking).
     fn init_early(_cpu_id: usize, _dtb: usize) {
         axcpu::init::init_trap();
+        axplat::console::set_cpu_id_fn(axplat_aarch64_common::current_cpu_hw_id);
         axplat_aarch64_common::pl011::init_early(phys_to_virt(pa!(UART_PADDR)));
         axplat_aarch64_common::generic_timer::init_early();
     }
@@ -31,6 +32,12 @@ impl InitIf for InitIfImpl {
     /// initialization (e.g, logging, memory management), and finalized the rest of
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _dtb: usize) {
+        #[cfg(feature = "klog")]
+        {
+            use crate::config::plat::{KLOG_PADDR, KLOG_SIZE};
+            // SAFETY: the range is reserved for the kernel log buffer only.
+            unsafe { axplat::console::klog::set_reserved_buffer((KLOG_PADDR, KLOG_SIZE)) };
+        }
         #[cfg(feature = "irq")]
         {
             use crate::mem::phys_to_virt;
@@ -42,7 +49,7 @@ impl InitIf for InitIfImpl {
             axplat_aarch64_common::generic_timer::enable_irqs(TIMER_IRQ);
 
             // enable UART IRQs
-            axplat::irq::register(UART_IRQ, axplat_aarch64_common::pl011::irq_handler);
+            axplat_aarch64_common::pl011::init_irq(UART_IRQ);
         }
     }
 
@@ -1,4 +1,21 @@
-use axplat::power::PowerIf;
+use axplat::power::{CpuIdleKind, CpuIdleState, PowerIf};
+
+use crate::config::devices::PM_PADDR;
+use crate::mem::phys_to_virt;
+
+/// Offset of the reset control register.
+const PM_RSTC: usize = 0x1c;
+/// Offset of the watchdog timer register.
+const PM_WDOG: usize = 0x24;
+/// Password required to write PM registers.
+const PM_PASSWORD: u32 = 0x5a00_0000;
+/// Reset configuration for a full reset in `PM_RSTC`.
+const PM_RSTC_WRCFG_FULL_RESET: u32 = 0x20;
+/// Mask of the reset configuration bits in `PM_RSTC`.
+const PM_RSTC_WRCFG_CLR: u32 = 0xffff_ffcf;
+
+/// Only the standby state is supported.
+const IDLE_STATES: &[CpuIdleState] = &[CpuIdleState::new("wfi", CpuIdleKind::Standby, 1, 1)];
 
 struct PowerImpl;
 
@@ -22,6 +39,27 @@ impl PowerIf for PowerImpl {
         }
     }
 
+    /// Parks the calling CPU core with interrupts disabled.
+    ///
+    /// Powering off a core is not supported with the spin-table boot method.
+    fn cpu_off() -> ! {
+        log::warn!("CPU power off is not supported, park the CPU");
+        axcpu::asm::disable_irqs();
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns all idle states supported by the calling CPU.
+    fn cpu_idle_states() -> &'static [CpuIdleState] {
+        IDLE_STATES
+    }
+
+    /// Puts the calling CPU into the idle state at `index`.
+    fn cpu_idle(_index: usize) {
+        axcpu::asm::wait_for_irqs();
+    }
+
     /// Shutdown the whole system.
     fn system_off() -> ! {
         log::info!("Shutting down...");
@@ -30,4 +68,22 @@ impl PowerIf for PowerImpl {
             axcpu::asm::halt();
         }
     }
+
+    /// Resets (reboots) the whole system by the PM watchdog.
+    fn system_reset() -> ! {
+        log::info!("Resetting...");
+        let pm_base = phys_to_virt(pa!(PM_PADDR));
+        let rstc = (pm_base + PM_RSTC).as_mut_ptr_of::<u32>();
+        let wdog = (pm_base + PM_WDOG).as_mut_ptr_of::<u32>();
+        unsafe {
+            // trigger a full reset after 10 watchdog ticks (~150us)
+            wdog.write_volatile(PM_PASSWORD | 10);
+            let val = rstc.read_volatile() & PM_RSTC_WRCFG_CLR;
+            rstc.write_volatile(PM_PASSWORD | val | PM_RSTC_WRCFG_FULL_RESET);
+        }
+        log::warn!("It should reset!");
+        loop {
+            axcpu::asm::halt();
+        }
+    }
 }
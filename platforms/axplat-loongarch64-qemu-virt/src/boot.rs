@@ -51,11 +51,16 @@ fn init_mmu() {
 /// The earliest entry point for the primary CPU.
 ///
 /// We can't use bl to jump to higher address, so we use jirl to jump to higher address.
+///
+/// The firmware (or QEMU's direct kernel boot) passes the physical address of
+/// the EFI system table in `$a2`, which is forwarded to [`axplat::call_main`].
 #[unsafe(naked)]
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.boot")]
 unsafe extern "C" fn _start() -> ! {
     core::arch::naked_asm!("
+        move        $s0, $a2            # save EFI system table pointer
+
         ori         $t0, $zero, 0x1     # CSR_DMW1_PLV0
         lu52i.d     $t0, $t0, -2048     # UC, PLV0, 0x8000 xxxx xxxx xxxx
         csrwr       $t0, 0x180          # LOONGARCH_CSR_DMWIN0
@@ -74,7 +79,7 @@ unsafe extern "C" fn _start() -> ! {
         bl          {init_mmu}          # setup boot page table and enable MMU
 
         csrrd       $a0, 0x20           # cpuid
-        li.d        $a1, 0              # TODO: parse dtb
+        move        $a1, $s0            # EFI system table
         la.global   $t0, {entry}
         jirl        $zero, $t0, 0",
         boot_stack_size = const BOOT_STACK_SIZE,
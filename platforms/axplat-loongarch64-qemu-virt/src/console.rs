@@ -1,12 +1,45 @@
 use crate::mem::phys_to_virt;
+use axplat::console::ConsoleRx;
 use kspin::SpinNoIrq;
 use memory_addr::PhysAddr;
 use ns16550a::Uart;
 
 const UART_BASE: PhysAddr = pa!(crate::config::devices::UART_PADDR);
 
+/// The interrupt of the UART, routed through the PCH-PIC and EIOINTC.
+#[cfg(feature = "irq")]
+const UART_IRQ: usize = crate::config::devices::UART_IRQ;
+
+/// Interrupt enable register.
+#[cfg(feature = "irq")]
+const IER: usize = 1;
+/// Enables the received data available interrupt.
+#[cfg(feature = "irq")]
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+
 static UART: SpinNoIrq<Uart> = SpinNoIrq::new(Uart::new(phys_to_virt(UART_BASE).as_usize()));
 
+static RX: ConsoleRx<256> = ConsoleRx::new();
+
+/// Enables the UART receive interrupt, after the PCH-PIC and EIOINTC are
+/// initialized.
+#[cfg(feature = "irq")]
+pub(crate) fn init_irq() {
+    if RX.register_irq(UART_IRQ, irq_handler) {
+        let uart = UART.lock();
+        let ier = (uart.base_address() + IER) as *mut u8;
+        unsafe { ier.write_volatile(IER_RX_AVAILABLE) };
+    } else {
+        warn!("failed to register the UART IRQ");
+    }
+}
+
+#[cfg(feature = "irq")]
+fn irq_handler() {
+    let uart = UART.lock();
+    RX.receive(|| uart.get());
+}
+
 use axplat::console::ConsoleIf;
 
 struct ConsoleIfImpl;
@@ -32,12 +65,13 @@ impl ConsoleIf for ConsoleIfImpl {
     /// Reads bytes from the console into the given mutable slice.
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize {
-        for (i, byte) in bytes.iter_mut().enumerate() {
-            match UART.lock().get() {
-                Some(c) => *byte = c,
-                None => return i,
-            }
-        }
-        bytes.len()
+        let uart = UART.lock();
+        RX.read(bytes, || uart.get())
+    }
+
+    /// Returns the IRQ number of the console input, or `None` if the console
+    /// input is not interrupt-driven.
+    fn irq_num() -> Option<usize> {
+        RX.irq_num()
     }
 }
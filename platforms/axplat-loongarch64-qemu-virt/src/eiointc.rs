@@ -0,0 +1,83 @@
+//! Extended I/O Interrupt Controller (EIOINTC) of the Loongson 3A CPU.
+//!
+//! All interrupt vectors are routed to the `HWI1` line of the boot CPU. The
+//! registers are accessed through IOCSR, see the Loongson 3A5000 processor
+//! user manual for the layout.
+
+use kspin::SpinNoIrq;
+use loongArch64::iocsr::{iocsr_read_d, iocsr_read_w, iocsr_write_d, iocsr_write_w};
+use loongArch64::register::ecfg::{self, LineBasedInterrupt};
+
+/// The number of interrupt vectors.
+pub const EIOINTC_NUM_VECTORS: usize = 256;
+
+/// The CPU interrupt (bit in `ESTAT.IS`) that all vectors are routed to.
+pub const EIOINTC_IRQ: usize = 3;
+
+/// Miscellaneous function register.
+const IOCSR_MISC_FUNC: usize = 0x420;
+/// Enables the extended I/O interrupts.
+const MISC_FUNC_EXT_IOI_EN: u64 = 1 << 48;
+
+/// CPU interrupt line registers, one byte per group of 32 vectors.
+const IPMAP: usize = 0x14c0;
+/// Enable registers, one bit per vector.
+const ENABLE: usize = 0x1600;
+/// Bounce registers, one bit per vector.
+const BOUNCE: usize = 0x1680;
+/// Interrupt status registers of the current CPU, one bit per vector. Bits are
+/// cleared by writing 1.
+const CORE_ISR: usize = 0x1800;
+/// Route registers, one byte per vector.
+const ROUTE: usize = 0x1c00;
+
+/// Selects the `HWI1` line in `IPMAP`.
+const IPMAP_HWI1: u32 = 1 << 1;
+
+/// Serializes read-modify-write accesses to the enable registers.
+static ENABLE_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+/// Routes all vectors to the given CPU, with all of them disabled.
+pub fn init(boot_cpu_id: usize) {
+    iocsr_write_d(
+        IOCSR_MISC_FUNC,
+        iocsr_read_d(IOCSR_MISC_FUNC) | MISC_FUNC_EXT_IOI_EN,
+    );
+    // The lower 4 bits select the core in the node, the higher 4 bits select
+    // the node.
+    let route = ((1 << (boot_cpu_id % 4)) | ((boot_cpu_id / 4) << 4)) as u32;
+    let route = route * 0x0101_0101;
+    for i in 0..EIOINTC_NUM_VECTORS / 4 {
+        iocsr_write_w(ROUTE + i * 4, route);
+    }
+    for i in 0..EIOINTC_NUM_VECTORS / 32 / 4 {
+        iocsr_write_w(IPMAP + i * 4, IPMAP_HWI1 * 0x0101_0101);
+    }
+    for i in 0..EIOINTC_NUM_VECTORS / 32 {
+        iocsr_write_w(ENABLE + i * 4, 0);
+        iocsr_write_w(BOUNCE + i * 4, 0);
+    }
+    ecfg::set_lie(ecfg::read().lie() | LineBasedInterrupt::HWI1);
+}
+
+/// Enables or disables the given vector.
+pub fn set_enable(vector: usize, enabled: bool) {
+    let offset = ENABLE + vector / 32 * 4;
+    let bit = 1 << (vector % 32);
+    let _guard = ENABLE_LOCK.lock();
+    let value = iocsr_read_w(offset);
+    iocsr_write_w(offset, if enabled { value | bit } else { value & !bit });
+}
+
+/// Claims a pending vector of the current CPU.
+pub fn claim() -> Option<usize> {
+    (0..EIOINTC_NUM_VECTORS / 32).find_map(|i| {
+        let pending = iocsr_read_w(CORE_ISR + i * 4);
+        if pending == 0 {
+            return None;
+        }
+        let bit = pending.trailing_zeros() as usize;
+        iocsr_write_w(CORE_ISR + i * 4, 1 << bit);
+        Some(i * 32 + bit)
+    })
+}
@@ -7,9 +7,11 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _mbi: usize) {
+    fn init_early(_cpu_id: usize, efi_systab: usize) {
         axcpu::init::init_trap();
+        axplat::console::set_cpu_id_fn(|| loongArch64::register::cpuid::read().core_id());
         crate::time::init_early();
+        crate::mem::init(efi_systab);
     }
 
     /// Initializes the platform at the early stage for secondary cores.
@@ -23,6 +25,17 @@ impl InitIf for InitIfImpl {
     /// initialization (e.g, logging, memory management), and finalized the rest of
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _arg: usize) {
+        #[cfg(feature = "klog")]
+        {
+            use crate::config::plat::{KLOG_PADDR, KLOG_SIZE};
+            // SAFETY: the range is reserved for the kernel log buffer only.
+            unsafe { axplat::console::klog::set_reserved_buffer((KLOG_PADDR, KLOG_SIZE)) };
+        }
+        #[cfg(feature = "irq")]
+        {
+            crate::irq::init_primary(_cpu_id);
+            crate::console::init_irq();
+        }
         crate::time::init_percpu();
     }
 
@@ -30,6 +43,8 @@ impl InitIf for InitIfImpl {
     fn init_later_secondary(_cpu_id: usize) {
         #[cfg(feature = "smp")]
         {
+            #[cfg(feature = "irq")]
+            crate::irq::init_percpu();
             crate::time::init_percpu();
         }
     }
@@ -1,28 +1,79 @@
-use axplat::irq::{HandlerTable, IrqHandler, IrqIf};
+//! Interrupt handling.
+//!
+//! Timer interrupts and IPIs are delivered to the CPU directly, with the bit in
+//! `ESTAT.IS` as the IRQ number. Device interrupts are collected by the
+//! PCH-PIC and delivered through the EIOINTC, with [`EXT_IRQ_BASE`] plus the
+//! PCH-PIC pin as the IRQ number.
+
+use axplat::irq::{HandlerTable, IpiIf, IpiTarget, IrqHandler, IrqIf};
+use loongArch64::consts::{
+    LOONGARCH_IOCSR_IPI_CLEAR, LOONGARCH_IOCSR_IPI_EN, LOONGARCH_IOCSR_IPI_STATUS,
+};
+use loongArch64::iocsr::{iocsr_read_w, iocsr_write_w};
+use loongArch64::ipi::send_ipi_single;
 use loongArch64::register::{
     ecfg::{self, LineBasedInterrupt},
     ticlr,
 };
 
+use crate::config::devices::{IPI_IRQ, TIMER_IRQ};
+use crate::eiointc::{self, EIOINTC_IRQ};
+use crate::pch_pic::{self, PCH_PIC_NUM_PINS};
+
+/// The IRQ number of PCH-PIC pin 0.
+pub const EXT_IRQ_BASE: usize = 64;
+
 /// The maximum number of IRQs.
-pub const MAX_IRQ_COUNT: usize = 12;
+pub const MAX_IRQ_COUNT: usize = EXT_IRQ_BASE + PCH_PIC_NUM_PINS;
+
+/// The IPI action (bit in `IPI_STATUS`) used for IPIs sent by [`IpiIf`].
+///
+/// Action 0 is used for booting secondary CPUs.
+const ACTION_IPI: u32 = 1 << 1;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// Initializes the interrupt controllers and routes all device interrupts to
+/// the boot CPU.
+pub(crate) fn init_primary(cpu_id: usize) {
+    pch_pic::init();
+    eiointc::init(cpu_id);
+    init_percpu();
+}
+
+pub(crate) fn init_percpu() {
+    // allow all IPI actions to raise the IPI interrupt
+    iocsr_write_w(LOONGARCH_IOCSR_IPI_EN, u32::MAX);
+}
+
 struct IrqIfImpl;
 
 #[impl_plat_interface]
 impl IrqIf for IrqIfImpl {
     /// Enables or disables the given IRQ.
     fn set_enable(irq_num: usize, enabled: bool) {
-        if irq_num == crate::config::devices::TIMER_IRQ {
-            let old_value = ecfg::read().lie();
-            let new_value = match enabled {
-                true => old_value | LineBasedInterrupt::TIMER,
-                false => old_value & !LineBasedInterrupt::TIMER,
-            };
-            ecfg::set_lie(new_value);
+        if irq_num >= EXT_IRQ_BASE {
+            let pin = irq_num - EXT_IRQ_BASE;
+            if pin < PCH_PIC_NUM_PINS {
+                // The PCH-PIC forwards the pin with itself as the vector.
+                eiointc::set_enable(pin, enabled);
+                pch_pic::set_enable(pin, enabled);
+            } else {
+                warn!("invalid device IRQ {}", irq_num);
+            }
+            return;
         }
+        let line = match irq_num {
+            TIMER_IRQ => LineBasedInterrupt::TIMER,
+            IPI_IRQ => LineBasedInterrupt::IPI,
+            _ => return,
+        };
+        let old_value = ecfg::read().lie();
+        let new_value = match enabled {
+            true => old_value | line,
+            false => old_value & !line,
+        };
+        ecfg::set_lie(new_value);
     }
 
     /// Registers an IRQ handler for the given IRQ.
@@ -50,8 +101,23 @@ impl IrqIf for IrqIfImpl {
     /// IRQ handler table and calls the corresponding handler. If necessary, it
     /// also acknowledges the interrupt controller after handling.
     fn handle(irq: usize) {
-        if irq == crate::config::devices::TIMER_IRQ {
-            ticlr::clear_timer_interrupt();
+        match irq {
+            TIMER_IRQ => ticlr::clear_timer_interrupt(),
+            IPI_IRQ => iocsr_write_w(
+                LOONGARCH_IOCSR_IPI_CLEAR,
+                iocsr_read_w(LOONGARCH_IOCSR_IPI_STATUS),
+            ),
+            EIOINTC_IRQ => {
+                while let Some(vector) = eiointc::claim() {
+                    let irq = EXT_IRQ_BASE + vector;
+                    trace!("IRQ: external {}", irq);
+                    if !IRQ_HANDLER_TABLE.handle(irq) {
+                        warn!("Unhandled IRQ {}", irq);
+                    }
+                }
+                return;
+            }
+            _ => {}
         }
         trace!("IRQ {}", irq);
         if !IRQ_HANDLER_TABLE.handle(irq) {
@@ -59,3 +125,26 @@ impl IrqIf for IrqIfImpl {
         }
     }
 }
+
+struct IpiIfImpl;
+
+#[impl_plat_interface]
+impl IpiIf for IpiIfImpl {
+    /// Returns the IRQ number of IPIs.
+    fn ipi_irq_num() -> usize {
+        IPI_IRQ
+    }
+
+    /// Sends an IPI to the given target CPU(s).
+    fn send_ipi(target: IpiTarget) {
+        match target {
+            IpiTarget::Cpu { cpu_id } => send_ipi_single(cpu_id, ACTION_IPI),
+            IpiTarget::AllExceptCurrent { cpu_id, cpu_num } => (0..cpu_num)
+                .filter(|&id| id != cpu_id)
+                .for_each(|id| send_ipi_single(id, ACTION_IPI)),
+            IpiTarget::All { cpu_num } => {
+                (0..cpu_num).for_each(|id| send_ipi_single(id, ACTION_IPI))
+            }
+        }
+    }
+}
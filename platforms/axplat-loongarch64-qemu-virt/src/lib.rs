@@ -18,11 +18,15 @@ mod config {
 
 mod boot;
 mod console;
+#[cfg(feature = "irq")]
+mod eiointc;
 mod init;
 #[cfg(feature = "irq")]
 mod irq;
 mod mem;
 #[cfg(feature = "smp")]
 mod mp;
+#[cfg(feature = "irq")]
+mod pch_pic;
 mod power;
 mod time;
@@ -1,21 +1,121 @@
-use axplat::mem::{MemIf, RawRange};
+use axplat::fdt::Fdt;
+use axplat::mem::{MemIf, RawRange, normalize_ranges, ranges_contain};
+use heapless::Vec;
+use lazyinit::LazyInit;
 use memory_addr::{PhysAddr, VirtAddr};
 
 use crate::config::devices::MMIO_RANGES;
-use crate::config::plat::{PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use crate::config::plat::{
+    KERNEL_BASE_PADDR, KERNEL_BASE_VADDR, KLOG_PADDR, KLOG_SIZE, PHYS_BUS_OFFSET, PHYS_MEMORY_BASE,
+    PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET,
+};
+
+/// The maximum number of RAM or reserved regions.
+const MAX_REGIONS: usize = 16;
+
+/// Signature of the EFI system table ("IBI SYST").
+const EFI_SYSTEM_TABLE_SIGNATURE: u64 = 0x5453_5953_2049_4249;
+/// GUID of the device tree configuration table (b1b621d5-f19c-41a5-830b-d9152c69aae0).
+const DEVICE_TREE_GUID: [u8; 16] = [
+    0xd5, 0x21, 0xb6, 0xb1, 0x9c, 0xf1, 0xa5, 0x41, 0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0,
+];
+
+static RAM_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
+static RESERVED_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
 
 struct MemIfImpl;
 
-#[allow(dead_code)]
 pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
 }
 
-#[allow(dead_code)]
 pub const fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
     pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
 }
 
+/// Returns the physical memory range where the kernel image is loaded.
+fn kernel_image_range() -> RawRange {
+    unsafe extern "C" {
+        fn _ekernel();
+    }
+    (KERNEL_BASE_PADDR, _ekernel as usize - KERNEL_BASE_VADDR)
+}
+
+/// Looks up the device tree blob in the configuration tables of the EFI
+/// system table, returns its physical address.
+fn find_dtb(efi_systab_paddr: usize) -> Option<usize> {
+    if efi_systab_paddr == 0 {
+        return None;
+    }
+    let systab = phys_to_virt(pa!(efi_systab_paddr)).as_ptr();
+    // SAFETY: the EFI system table is passed by the firmware and is mapped
+    // by the boot page table.
+    unsafe {
+        if (systab as *const u64).read_unaligned() != EFI_SYSTEM_TABLE_SIGNATURE {
+            warn!("Invalid EFI system table at {efi_systab_paddr:#x}");
+            return None;
+        }
+        let nr_tables = (systab.add(104) as *const usize).read_unaligned();
+        let tables = (systab.add(112) as *const usize).read_unaligned();
+        let tables = phys_to_virt(pa!(tables)).as_ptr();
+        (0..nr_tables).find_map(|i| {
+            let entry = tables.add(i * 24);
+            let guid = (entry as *const [u8; 16]).read_unaligned();
+            (guid == DEVICE_TREE_GUID).then(|| (entry.add(16) as *const usize).read_unaligned())
+        })
+    }
+}
+
+/// Parses the device tree blob found in the EFI system table to get the
+/// physical memory layout.
+///
+/// If no valid blob is found, `PHYS_MEMORY_BASE` and `PHYS_MEMORY_SIZE` in
+/// the configuration are used instead.
+pub(crate) fn init(efi_systab_paddr: usize) {
+    let mut ram = Vec::new();
+    let mut reserved: Vec<RawRange, MAX_REGIONS> = Vec::new();
+
+    let fdt = find_dtb(efi_systab_paddr).and_then(|dtb_paddr| {
+        unsafe { Fdt::from_ptr(phys_to_virt(pa!(dtb_paddr)).as_ptr()) }
+            .inspect_err(|e| warn!("Invalid DTB at {dtb_paddr:#x}: {e}"))
+            .ok()
+            .map(|fdt| (dtb_paddr, fdt))
+    });
+    if let Some((dtb_paddr, fdt)) = fdt {
+        for r in fdt.memory_regions().filter(|r| r.1 > 0) {
+            if ram.push(r).is_err() {
+                warn!("Too many RAM regions in DTB, ignore {r:#x?}");
+            }
+        }
+        for r in fdt.reserved_regions_with_blob(dtb_paddr) {
+            if reserved.push(r).is_err() {
+                warn!("Too many reserved regions in DTB, ignore {r:#x?}");
+            }
+        }
+    }
+
+    if ram.is_empty() {
+        ram.push((PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE)).unwrap();
+    }
+    ram.sort_unstable_by_key(|r| r.0);
+    if cfg!(feature = "klog") && KLOG_SIZE > 0 {
+        let klog = (KLOG_PADDR, KLOG_SIZE);
+        if !ranges_contain(&ram, klog) {
+            warn!("klog range {klog:#x?} is not in RAM, ignore it");
+        } else if reserved.push(klog).is_err() {
+            warn!("Too many reserved regions, ignore klog range {klog:#x?}");
+        }
+    }
+    RAM_REGIONS.init_once(ram);
+    let mut res = Vec::new();
+    normalize_ranges(&mut reserved, &[kernel_image_range()], |r| {
+        if res.push(r).is_err() {
+            warn!("Too many reserved regions, ignore {r:#x?}");
+        }
+    });
+    RESERVED_REGIONS.init_once(res);
+}
+
 #[impl_plat_interface]
 impl MemIf for MemIfImpl {
     /// Returns all physical memory (RAM) ranges on the platform.
@@ -23,7 +123,7 @@ impl MemIf for MemIfImpl {
     /// All memory ranges except reserved ranges (including the kernel loaded
     /// range) are free for allocation.
     fn phys_ram_ranges() -> &'static [RawRange] {
-        &[(PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE)]
+        RAM_REGIONS.as_slice()
     }
 
     /// Returns all reserved physical memory ranges on the platform.
@@ -34,11 +134,26 @@ impl MemIf for MemIfImpl {
     /// Note that the ranges returned should not include the range where the
     /// kernel is loaded.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[]
+        RESERVED_REGIONS.as_slice()
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange] {
         &MMIO_RANGES
     }
+
+    /// Translates a physical address to a virtual address.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        phys_to_virt(paddr)
+    }
+
+    /// Translates a virtual address to a physical address.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+        virt_to_phys(vaddr)
+    }
+
+    /// Returns the offset between bus addresses and physical addresses.
+    fn phys_bus_offset() -> usize {
+        PHYS_BUS_OFFSET
+    }
 }
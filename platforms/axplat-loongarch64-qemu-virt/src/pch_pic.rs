@@ -0,0 +1,84 @@
+//! Platform Controller Hub Programmable Interrupt Controller (PCH-PIC) of the
+//! Loongson 7A bridge.
+//!
+//! Each input pin is forwarded to the EIOINTC as an HyperTransport interrupt
+//! message, whose vector is the pin number. See the Loongson 7A1000 bridge
+//! user manual for the register layout.
+
+use kspin::SpinNoIrq;
+use memory_addr::PhysAddr;
+
+use crate::config::devices::PCH_PIC_PADDR;
+use crate::mem::phys_to_virt;
+
+const PCH_PIC_BASE: PhysAddr = pa!(PCH_PIC_PADDR);
+
+/// The number of input pins.
+pub const PCH_PIC_NUM_PINS: usize = 64;
+
+/// Interrupt mask registers, one bit per pin. A set bit masks the pin.
+const INT_MASK: usize = 0x020;
+/// Enables forwarding the pin as an HyperTransport message, one bit per pin.
+const HTMSI_EN: usize = 0x040;
+/// Trigger mode registers, one bit per pin. A set bit selects edge trigger.
+const INT_EDGE: usize = 0x060;
+/// Clears the pending edge-triggered interrupts, one bit per pin.
+const INT_CLEAR: usize = 0x080;
+/// Auto-rotation registers of the two output lines, one bit per pin.
+const AUTO_CTRL0: usize = 0x0c0;
+const AUTO_CTRL1: usize = 0x0e0;
+/// Route registers, one byte per pin.
+const ROUTE_ENTRY: usize = 0x100;
+/// HyperTransport message vector registers, one byte per pin.
+const HTMSI_VECTOR: usize = 0x200;
+/// Polarity registers, one bit per pin. A set bit selects active-low.
+const INT_POLARITY: usize = 0x3e0;
+
+/// Routes a pin to the `INT0` output line.
+const ROUTE_INT0: u8 = 1;
+
+/// Serializes read-modify-write accesses to the mask registers.
+static MASK_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+fn reg<T>(offset: usize) -> *mut T {
+    (phys_to_virt(PCH_PIC_BASE) + offset).as_mut_ptr_of()
+}
+
+fn read(offset: usize) -> u32 {
+    unsafe { reg::<u32>(offset).read_volatile() }
+}
+
+fn write(offset: usize, value: u32) {
+    unsafe { reg::<u32>(offset).write_volatile(value) }
+}
+
+fn write_byte(offset: usize, value: u8) {
+    unsafe { reg::<u8>(offset).write_volatile(value) }
+}
+
+/// Masks all pins, and forwards them as active-high level-triggered
+/// interrupts with the pin number as the vector.
+pub fn init() {
+    for pin in 0..PCH_PIC_NUM_PINS {
+        write_byte(HTMSI_VECTOR + pin, pin as u8);
+        write_byte(ROUTE_ENTRY + pin, ROUTE_INT0);
+    }
+    for i in 0..PCH_PIC_NUM_PINS / 32 {
+        write(INT_MASK + i * 4, u32::MAX);
+        write(INT_CLEAR + i * 4, u32::MAX);
+        write(INT_EDGE + i * 4, 0);
+        write(INT_POLARITY + i * 4, 0);
+        write(AUTO_CTRL0 + i * 4, 0);
+        write(AUTO_CTRL1 + i * 4, 0);
+        write(HTMSI_EN + i * 4, u32::MAX);
+    }
+}
+
+/// Unmasks or masks the given pin.
+pub fn set_enable(pin: usize, enabled: bool) {
+    let offset = INT_MASK + pin / 32 * 4;
+    let bit = 1 << (pin % 32);
+    let _guard = MASK_LOCK.lock();
+    let mask = read(offset);
+    write(offset, if enabled { mask & !bit } else { mask | bit });
+}
@@ -1,4 +1,12 @@
-use axplat::power::PowerIf;
+use axplat::power::{CpuIdleKind, CpuIdleState, PowerIf};
+
+/// Offset of the reset register in the generic event device (GED).
+const GED_REG_RESET: usize = 0x02;
+/// Value written to the reset register to reset the system.
+const GED_RESET_VALUE: u8 = 0x42;
+
+/// Only the standby state is supported.
+const IDLE_STATES: &[CpuIdleState] = &[CpuIdleState::new("idle", CpuIdleKind::Standby, 1, 1)];
 
 struct PowerImpl;
 
@@ -14,6 +22,27 @@ impl PowerIf for PowerImpl {
         crate::mp::start_secondary_cpu(_cpu_id, pa!(_stack_top_paddr));
     }
 
+    /// Parks the calling CPU core with interrupts disabled.
+    ///
+    /// Powering off a core is not supported on this platform.
+    fn cpu_off() -> ! {
+        warn!("CPU power off is not supported, park the CPU");
+        axcpu::asm::disable_irqs();
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns all idle states supported by the calling CPU.
+    fn cpu_idle_states() -> &'static [CpuIdleState] {
+        IDLE_STATES
+    }
+
+    /// Puts the calling CPU into the idle state at `index`.
+    fn cpu_idle(_index: usize) {
+        axcpu::asm::wait_for_irqs();
+    }
+
     /// Shutdown the whole system.
     fn system_off() -> ! {
         const HALT_ADDR: *mut u8 =
@@ -27,4 +56,19 @@ impl PowerIf for PowerImpl {
             axcpu::asm::halt();
         }
     }
+
+    /// Resets (reboots) the whole system.
+    fn system_reset() -> ! {
+        const RESET_ADDR: *mut u8 =
+            crate::mem::phys_to_virt(pa!(crate::config::devices::GED_PADDR + GED_REG_RESET))
+                .as_mut_ptr();
+
+        info!("Resetting...");
+        unsafe { RESET_ADDR.write_volatile(GED_RESET_VALUE) };
+        axcpu::asm::halt();
+        warn!("It should reset!");
+        loop {
+            axcpu::asm::halt();
+        }
+    }
 }
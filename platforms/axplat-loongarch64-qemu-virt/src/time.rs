@@ -1,8 +1,8 @@
-use axplat::time::TimeIf;
+use axplat::time::{ClockSource, TimeIf};
 use lazyinit::LazyInit;
 use loongArch64::time::Time;
 
-static NANOS_PER_TICK: LazyInit<u64> = LazyInit::new();
+static CLOCK: LazyInit<ClockSource> = LazyInit::new();
 
 /// RTC wall time offset in nanoseconds at monotonic time base.
 static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
@@ -80,8 +80,10 @@ fn init_rtc() {
 }
 
 pub(super) fn init_early() {
-    NANOS_PER_TICK
-        .init_once(axplat::time::NANOS_PER_SEC / loongArch64::time::get_timer_freq() as u64);
+    CLOCK.init_once(ClockSource::new(
+        loongArch64::time::get_timer_freq() as u64,
+        64,
+    ));
 
     #[cfg(feature = "rtc")]
     init_rtc();
@@ -103,12 +105,12 @@ impl TimeIf for TimeIfImpl {
 
     /// Converts hardware ticks to nanoseconds.
     fn ticks_to_nanos(ticks: u64) -> u64 {
-        ticks * *NANOS_PER_TICK
+        CLOCK.ticks_to_nanos(ticks)
     }
 
     /// Converts nanoseconds to hardware ticks.
     fn nanos_to_ticks(nanos: u64) -> u64 {
-        nanos / *NANOS_PER_TICK
+        CLOCK.nanos_to_ticks(nanos)
     }
 
     /// Set a one-shot timer.
@@ -0,0 +1,166 @@
+//! Advanced Interrupt Architecture (AIA): APLIC in MSI delivery mode and IMSIC.
+//!
+//! It is used instead of the PLIC if the `aia` feature is enabled, and requires
+//! QEMU to be started with `-machine virt,aia=aplic-imsic`.
+//!
+//! Wired interrupts are converted to MSIs by the S-mode APLIC domain, with the
+//! source number as the interrupt identity, and written to the S-mode interrupt
+//! file of the boot hart. The remaining identities ([`MSI_IRQS`]) can be used by
+//! MSI-capable devices, which write the identity to [`msi_address`]. See the
+//! [RISC-V AIA specification](https://github.com/riscv/riscv-aia) for the
+//! register layout.
+
+use core::arch::asm;
+use core::ops::Range;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use memory_addr::PhysAddr;
+
+use crate::config::devices::{APLIC_PADDR, IMSIC_PADDR};
+use crate::mem::phys_to_virt;
+
+const APLIC_BASE: PhysAddr = pa!(APLIC_PADDR);
+const IMSIC_BASE: PhysAddr = pa!(IMSIC_PADDR);
+
+/// The number of wired interrupt sources of the APLIC, including the reserved
+/// source 0.
+pub const APLIC_NUM_SOURCES: usize = 96;
+
+/// The number of interrupt identities of an IMSIC interrupt file, including the
+/// reserved identity 0.
+pub const IMSIC_NUM_IDS: usize = 256;
+
+/// Interrupt identities that are not used by wired interrupts, and can be
+/// assigned to MSIs of devices.
+pub const MSI_IRQS: Range<usize> = APLIC_NUM_SOURCES..IMSIC_NUM_IDS;
+
+/// The size of the interrupt file of a hart.
+const IMSIC_FILE_SIZE: usize = 0x1000;
+
+/// Domain configuration register.
+const DOMAINCFG: usize = 0x0000;
+/// Source configuration registers of sources 1 and up, one word per source.
+const SOURCECFG_BASE: usize = 0x0004;
+/// Enables a source by its number.
+const SETIENUM: usize = 0x1edc;
+/// Disables a source by its number.
+const CLRIENUM: usize = 0x1fdc;
+/// Sets the pending bit of a source by its number, if it is level-sensitive
+/// and still asserted.
+const SETIPNUM_LE: usize = 0x2000;
+/// Target registers of sources 1 and up, one word per source.
+const TARGET_BASE: usize = 0x3004;
+
+/// Enables interrupts of the domain.
+const DOMAINCFG_IE: u32 = 1 << 8;
+/// Delivers interrupts by MSIs.
+const DOMAINCFG_DM_MSI: u32 = 1 << 2;
+/// Active-high level-sensitive source.
+const SOURCECFG_LEVEL_HIGH: u32 = 6;
+const TARGET_HART_SHIFT: u32 = 18;
+
+/// Interrupt delivery enable register of the interrupt file.
+const EIDELIVERY: usize = 0x70;
+/// Interrupt enable threshold register of the interrupt file.
+const EITHRESHOLD: usize = 0x72;
+/// Interrupt enable registers of the interrupt file. Only the even-numbered
+/// ones exist on RV64, each with 64 bits.
+const EIE0: usize = 0xc0;
+
+/// The hart that external interrupts are routed to.
+static TARGET_HART: AtomicUsize = AtomicUsize::new(0);
+
+fn aplic_write(offset: usize, value: u32) {
+    unsafe {
+        (phys_to_virt(APLIC_BASE) + offset)
+            .as_mut_ptr_of::<u32>()
+            .write_volatile(value)
+    }
+}
+
+/// Writes an indirect register of the interrupt file of the current hart.
+fn imsic_write(reg: usize, value: usize) {
+    unsafe {
+        asm!(
+            "csrw 0x150, {reg}",   // siselect
+            "csrw 0x151, {value}", // sireg
+            reg = in(reg) reg,
+            value = in(reg) value,
+        )
+    }
+}
+
+/// Routes external interrupts to the given hart, and switches the APLIC to MSI
+/// delivery mode.
+pub fn init(boot_hart_id: usize) {
+    TARGET_HART.store(boot_hart_id, Ordering::Release);
+    aplic_write(DOMAINCFG, DOMAINCFG_IE | DOMAINCFG_DM_MSI);
+}
+
+/// Enables interrupt delivery from the interrupt file of the current hart, and
+/// accepts all interrupt identities.
+///
+/// Individual interrupts are masked in the APLIC (for wired interrupts) or by
+/// the devices (for MSIs).
+pub fn init_percpu(_hart_id: usize) {
+    imsic_write(EITHRESHOLD, 0);
+    for i in 0..IMSIC_NUM_IDS / 64 {
+        imsic_write(EIE0 + 2 * i, usize::MAX);
+    }
+    imsic_write(EIDELIVERY, 1);
+}
+
+/// Enables or disables the given interrupt.
+///
+/// Only wired interrupts can be enabled or disabled, in the APLIC. MSIs are
+/// always enabled.
+pub fn set_enable(irq: usize, enabled: bool) {
+    if irq == 0 || irq >= IMSIC_NUM_IDS {
+        warn!("invalid AIA interrupt identity {irq}");
+        return;
+    }
+    if irq >= APLIC_NUM_SOURCES {
+        return;
+    }
+    if enabled {
+        let hart_id = TARGET_HART.load(Ordering::Acquire);
+        aplic_write(SOURCECFG_BASE + (irq - 1) * 4, SOURCECFG_LEVEL_HIGH);
+        aplic_write(
+            TARGET_BASE + (irq - 1) * 4,
+            ((hart_id as u32) << TARGET_HART_SHIFT) | irq as u32,
+        );
+        aplic_write(SETIENUM, irq as u32);
+    } else {
+        aplic_write(CLRIENUM, irq as u32);
+    }
+}
+
+/// Claims the highest priority pending interrupt of the current hart.
+pub fn claim() -> Option<usize> {
+    let topei: usize;
+    // SAFETY: reading and writing `stopei` claims the top interrupt.
+    unsafe { asm!("csrrw {}, 0x15c, zero", out(reg) topei) };
+    match (topei >> 16) & 0x7ff {
+        0 => None,
+        irq => Some(irq),
+    }
+}
+
+/// Signals the completion of a claimed interrupt.
+///
+/// The APLIC does not send another MSI for a level-sensitive source that is
+/// still asserted, so the source is pending again in that case.
+pub fn complete(irq: usize) {
+    if irq < APLIC_NUM_SOURCES {
+        aplic_write(SETIPNUM_LE, irq as u32);
+    }
+}
+
+/// Returns the physical address that MSI-capable devices write interrupt
+/// identities in [`MSI_IRQS`] to.
+///
+/// It is the S-mode interrupt file of the hart that external interrupts are
+/// routed to.
+pub fn msi_address() -> PhysAddr {
+    IMSIC_BASE + TARGET_HART.load(Ordering::Acquire) * IMSIC_FILE_SIZE
+}
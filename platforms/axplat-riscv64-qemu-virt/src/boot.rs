@@ -1,3 +1,5 @@
+use axplat::mem::RawRange;
+
 use crate::config::plat::{BOOT_STACK_SIZE, PHYS_VIRT_OFFSET};
 
 #[unsafe(link_section = ".bss.stack")]
@@ -6,6 +8,10 @@ static mut BOOT_STACK: [u8; BOOT_STACK_SIZE] = [0; BOOT_STACK_SIZE];
 #[unsafe(link_section = ".data.boot_page_table")]
 static mut BOOT_PT_SV39: [u64; 512] = [0; 512];
 
+/// Physical memory ranges mapped at `PHYS_VIRT_OFFSET` by the boot page table.
+pub(crate) const BOOT_MAPPED_RANGES: [RawRange; 2] =
+    [(0x0000_0000, 0x4000_0000), (0x8000_0000, 0x4000_0000)];
+
 #[allow(clippy::identity_op)] // (0x0 << 10) here makes sense because it's an address
 unsafe fn init_boot_page_table() {
     unsafe {
@@ -1,10 +1,18 @@
 use memory_addr::VirtAddr;
 
 use crate::mem::virt_to_phys;
+use crate::sbi::{SbiExtension, sbi_info};
 
 /// The maximum number of bytes that can be read at once.
 const MAX_RW_SIZE: usize = 256;
 
+/// Returns whether the SBI debug console (DBCN) extension is available.
+///
+/// Otherwise, the legacy `console_putchar` and `console_getchar` are used.
+fn has_dbcn() -> bool {
+    sbi_info().has_extension(SbiExtension::Console)
+}
+
 /// Tries to write bytes to the console from input u8 slice.
 /// Returns the number of bytes written.
 fn try_write_bytes(bytes: &[u8]) -> usize {
@@ -18,6 +26,30 @@ fn try_write_bytes(bytes: &[u8]) -> usize {
     .value
 }
 
+/// Writes bytes to the console by the legacy `console_putchar`.
+#[allow(deprecated)]
+fn legacy_write_bytes(bytes: &[u8]) {
+    for &c in bytes {
+        sbi_rt::legacy::console_putchar(c as usize);
+    }
+}
+
+/// Reads bytes from the console by the legacy `console_getchar`.
+/// Returns the number of bytes read.
+#[allow(deprecated)]
+fn legacy_read_bytes(bytes: &mut [u8]) -> usize {
+    let mut read_len = 0;
+    while read_len < bytes.len() {
+        // returns -1 if there is no input
+        match sbi_rt::legacy::console_getchar() {
+            c if c <= u8::MAX as usize => bytes[read_len] = c as u8,
+            _ => break,
+        }
+        read_len += 1;
+    }
+    read_len
+}
+
 use axplat::console::ConsoleIf;
 
 struct ConsoleIfImpl;
@@ -26,6 +58,13 @@ struct ConsoleIfImpl;
 impl ConsoleIf for ConsoleIfImpl {
     /// Writes bytes to the console from input u8 slice.
     fn write_bytes(bytes: &[u8]) {
+        #[cfg(feature = "ns16550")]
+        if crate::ns16550::is_enabled() {
+            return crate::ns16550::write_bytes(bytes);
+        }
+        if !has_dbcn() {
+            return legacy_write_bytes(bytes);
+        }
         let mut write_len = 0;
         let mut buf = [0; MAX_RW_SIZE];
         while write_len < bytes.len() {
@@ -43,6 +82,13 @@ impl ConsoleIf for ConsoleIfImpl {
     /// Reads bytes from the console into the given mutable slice.
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize {
+        #[cfg(feature = "ns16550")]
+        if crate::ns16550::is_enabled() {
+            return crate::ns16550::read_bytes(bytes);
+        }
+        if !has_dbcn() {
+            return legacy_read_bytes(bytes);
+        }
         sbi_rt::console_read(sbi_rt::Physical::new(
             bytes.len().min(MAX_RW_SIZE),
             virt_to_phys(VirtAddr::from_mut_ptr_of(bytes.as_mut_ptr())).as_usize(),
@@ -50,4 +96,17 @@ impl ConsoleIf for ConsoleIfImpl {
         ))
         .value
     }
+
+    /// Returns the IRQ number of the console input, or `None` if the console
+    /// input is not interrupt-driven.
+    ///
+    /// SBI calls provide no input interrupt, so the console input is always
+    /// polled unless it is switched to the UART by the `ns16550` feature.
+    fn irq_num() -> Option<usize> {
+        #[cfg(feature = "ns16550")]
+        if crate::ns16550::is_enabled() {
+            return crate::ns16550::irq_num();
+        }
+        None
+    }
 }
@@ -7,8 +7,12 @@ impl InitIf for InitIfImpl {
     /// This function should be called immediately after the kernel has booted,
     /// and performed earliest platform configuration and initialization (e.g.,
     /// early console, clocking).
-    fn init_early(_cpu_id: usize, _mbi: usize) {
+    fn init_early(_cpu_id: usize, dtb: usize) {
+        // The hart ID cannot be read in S-mode, so the kernel provides the
+        // current CPU ID to the console by `axplat::console::set_cpu_id_fn`.
         axcpu::init::init_trap();
+        crate::sbi::init_early();
+        crate::mem::init(dtb);
         crate::time::init_early();
     }
 
@@ -23,8 +27,20 @@ impl InitIf for InitIfImpl {
     /// initialization (e.g, logging, memory management), and finalized the rest of
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _arg: usize) {
+        #[cfg(feature = "klog")]
+        {
+            use crate::config::plat::{KLOG_PADDR, KLOG_SIZE};
+            // SAFETY: the range is reserved for the kernel log buffer only.
+            unsafe { axplat::console::klog::set_reserved_buffer((KLOG_PADDR, KLOG_SIZE)) };
+        }
         #[cfg(feature = "irq")]
-        crate::irq::init_percpu();
+        crate::irq::init_primary(_cpu_id);
+        #[cfg(feature = "ns16550")]
+        {
+            crate::ns16550::init();
+            #[cfg(feature = "irq")]
+            crate::ns16550::init_irq();
+        }
         crate::time::init_percpu();
     }
 
@@ -33,7 +49,7 @@ impl InitIf for InitIfImpl {
         #[cfg(feature = "smp")]
         {
             #[cfg(feature = "irq")]
-            crate::irq::init_percpu();
+            crate::irq::init_percpu(_cpu_id);
             crate::time::init_percpu();
         }
     }
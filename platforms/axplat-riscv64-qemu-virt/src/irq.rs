@@ -1,14 +1,22 @@
-//! TODO: PLIC
+//! Interrupt handling.
+//!
+//! Timer and software interrupts are delivered to the hart directly, while
+//! device interrupts are delivered as supervisor external interrupts and
+//! dispatched by the PLIC, or by the AIA if the `aia` feature is enabled.
 
-use axplat::irq::{HandlerTable, IrqHandler, IrqIf};
+use axplat::irq::{HandlerTable, IpiIf, IpiTarget, IrqHandler, IrqIf};
 use core::sync::atomic::{AtomicPtr, Ordering};
-use riscv::register::sie;
+use riscv::register::{sie, sip};
+
+#[cfg(feature = "aia")]
+use crate::aia as intc;
+#[cfg(not(feature = "aia"))]
+use crate::plic as intc;
 
 /// `Interrupt` bit in `scause`
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
 
 /// Supervisor software interrupt in `scause`
-#[allow(unused)]
 pub(super) const S_SOFT: usize = INTC_IRQ_BASE + 1;
 
 /// Supervisor timer interrupt in `scause`
@@ -19,15 +27,22 @@ pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
 
 static TIMER_HANDLER: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
 
+static IPI_HANDLER: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// The maximum number of IRQs.
+#[cfg(not(feature = "aia"))]
+pub const MAX_IRQ_COUNT: usize = intc::PLIC_NUM_SOURCES;
 /// The maximum number of IRQs.
-pub const MAX_IRQ_COUNT: usize = 1024;
+#[cfg(feature = "aia")]
+pub const MAX_IRQ_COUNT: usize = intc::IMSIC_NUM_IDS;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
 macro_rules! with_cause {
-    ($cause: expr, @S_TIMER => $timer_op: expr, @S_EXT => $ext_op: expr, @EX_IRQ => $plic_op: expr $(,)?) => {
+    ($cause: expr, @S_TIMER => $timer_op: expr, @S_SOFT => $ipi_op: expr, @S_EXT => $ext_op: expr, @EX_IRQ => $plic_op: expr $(,)?) => {
         match $cause {
             S_TIMER => $timer_op,
+            S_SOFT => $ipi_op,
             S_EXT => $ext_op,
             other => {
                 if other & INTC_IRQ_BASE == 0 {
@@ -42,7 +57,14 @@ macro_rules! with_cause {
     };
 }
 
-pub(super) fn init_percpu() {
+/// Initializes the interrupt controller and routes all external interrupts to the boot hart.
+pub(super) fn init_primary(hart_id: usize) {
+    intc::init(hart_id);
+    init_percpu(hart_id);
+}
+
+pub(super) fn init_percpu(hart_id: usize) {
+    intc::init_percpu(hart_id);
     // enable soft interrupts, timer interrupts, and external interrupts
     unsafe {
         sie::set_ssoft();
@@ -56,9 +78,15 @@ struct IrqIfImpl;
 #[impl_plat_interface]
 impl IrqIf for IrqIfImpl {
     /// Enables or disables the given IRQ.
-    fn set_enable(irq: usize, _enabled: bool) {
-        // TODO: set enable in PLIC
-        warn!("set_enable is not implemented for IRQ {}", irq);
+    ///
+    /// Only device-side IRQs can be enabled or disabled, in the interrupt
+    /// controller.
+    fn set_enable(irq: usize, enabled: bool) {
+        if irq & INTC_IRQ_BASE == 0 {
+            intc::set_enable(irq, enabled);
+        } else {
+            warn!("set_enable is not supported for CPU-side IRQ {:#x}", irq);
+        }
     }
 
     /// Registers an IRQ handler for the given IRQ.
@@ -78,6 +106,7 @@ impl IrqIf for IrqIfImpl {
         with_cause!(
             irq,
             @S_TIMER => TIMER_HANDLER.compare_exchange(core::ptr::null_mut(), handler as *mut _, Ordering::AcqRel, Ordering::Acquire).is_ok(),
+            @S_SOFT => IPI_HANDLER.compare_exchange(core::ptr::null_mut(), handler as *mut _, Ordering::AcqRel, Ordering::Acquire).is_ok(),
             @S_EXT => {
                 warn!("External IRQ should be got from PLIC, not scause");
                 false
@@ -109,11 +138,25 @@ impl IrqIf for IrqIfImpl {
                     None
                 }
             },
+            @S_SOFT => {
+                let handler = IPI_HANDLER.swap(core::ptr::null_mut(), Ordering::AcqRel);
+                if !handler.is_null() {
+                    Some(unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler) })
+                } else {
+                    None
+                }
+            },
             @S_EXT => {
                 warn!("External IRQ should be got from PLIC, not scause");
                 None
             },
-            @EX_IRQ => IRQ_HANDLER_TABLE.unregister_handler(irq)
+            @EX_IRQ => {
+                let handler = IRQ_HANDLER_TABLE.unregister_handler(irq);
+                if handler.is_some() {
+                    Self::set_enable(irq, false);
+                }
+                handler
+            }
         )
     }
 
@@ -133,10 +176,23 @@ impl IrqIf for IrqIfImpl {
                     unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler)() };
                 }
             },
+            @S_SOFT => {
+                trace!("IRQ: IPI");
+                unsafe { sip::clear_ssoft() };
+                let handler = IPI_HANDLER.load(Ordering::Acquire);
+                if !handler.is_null() {
+                    // SAFETY: The handler is guaranteed to be a valid function pointer.
+                    unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler)() };
+                }
+            },
             @S_EXT => {
-                // TODO: get IRQ number from PLIC
-                if !IRQ_HANDLER_TABLE.handle(0) {
-                    warn!("Unhandled IRQ {}", 0);
+                // External IRQs are only routed to the target hart.
+                while let Some(irq) = intc::claim() {
+                    trace!("IRQ: external {}", irq);
+                    if !IRQ_HANDLER_TABLE.handle(irq) {
+                        warn!("Unhandled IRQ {}", irq);
+                    }
+                    intc::complete(irq);
                 }
             },
             @EX_IRQ => {
@@ -145,3 +201,45 @@ impl IrqIf for IrqIfImpl {
         )
     }
 }
+
+/// Sends an IPI to harts `base..base + usize::BITS` whose bits are set in
+/// `mask`.
+fn send_ipi_mask(mask: usize, base: usize) {
+    if mask != 0 {
+        let ret = sbi_rt::send_ipi(sbi_rt::HartMask::from_mask_base(mask, base));
+        if ret.is_err() {
+            warn!("SBI send_ipi failed: {:?}", ret.error);
+        }
+    }
+}
+
+struct IpiIfImpl;
+
+#[impl_plat_interface]
+impl IpiIf for IpiIfImpl {
+    /// Returns the IRQ number of IPIs.
+    fn ipi_irq_num() -> usize {
+        S_SOFT
+    }
+
+    /// Sends an IPI to the given target CPU(s).
+    ///
+    /// CPU IDs are used as the hart IDs.
+    fn send_ipi(target: IpiTarget) {
+        let (cpu_num, except) = match target {
+            IpiTarget::Cpu { cpu_id } => return send_ipi_mask(1, cpu_id),
+            IpiTarget::AllExceptCurrent { cpu_id, cpu_num } => (cpu_num, Some(cpu_id)),
+            IpiTarget::All { cpu_num } => (cpu_num, None),
+        };
+        for base in (0..cpu_num).step_by(usize::BITS as usize) {
+            let mut mask = match cpu_num - base {
+                n if n >= usize::BITS as usize => usize::MAX,
+                n => (1 << n) - 1,
+            };
+            if let Some(id) = except.filter(|id| (base..base + usize::BITS as usize).contains(id)) {
+                mask &= !(1 << (id - base));
+            }
+            send_ipi_mask(mask, base);
+        }
+    }
+}
@@ -7,15 +7,27 @@ extern crate axplat;
 #[macro_use]
 extern crate memory_addr;
 
+#[cfg(feature = "aia")]
+mod aia;
 mod boot;
 mod console;
 mod init;
 #[cfg(feature = "irq")]
 mod irq;
 mod mem;
+#[cfg(feature = "ns16550")]
+mod ns16550;
+#[cfg(all(feature = "irq", not(feature = "aia")))]
+mod plic;
 mod power;
+mod sbi;
 mod time;
 
+pub use self::sbi::{SbiExtension, SbiInfo, sbi_info};
+
+#[cfg(feature = "aia")]
+pub use self::aia::{MSI_IRQS, msi_address};
+
 mod config {
     axconfig_macros::include_configs!(path_env = "AX_CONFIG_PATH", fallback = "axconfig.toml");
     assert_str_eq!(
@@ -1,21 +1,153 @@
-use axplat::mem::{MemIf, RawRange};
+use axplat::fdt::Fdt;
+use axplat::mem::{MemIf, RawRange, normalize_ranges, ranges_contain};
+use heapless::Vec;
+use lazyinit::LazyInit;
 use memory_addr::{PhysAddr, VirtAddr};
 
+use crate::boot::BOOT_MAPPED_RANGES;
 use crate::config::devices::MMIO_RANGES;
-use crate::config::plat::{KERNEL_BASE_PADDR, PHYS_MEMORY_SIZE, PHYS_VIRT_OFFSET};
+use crate::config::plat::{
+    KERNEL_BASE_PADDR, KERNEL_BASE_VADDR, KLOG_PADDR, KLOG_SIZE, PHYS_BUS_OFFSET, PHYS_MEMORY_SIZE,
+    PHYS_VIRT_OFFSET,
+};
+
+/// The maximum number of RAM or reserved regions.
+const MAX_REGIONS: usize = 16;
+
+/// The magic number at the beginning of a device tree blob.
+const FDT_MAGIC: u32 = 0xd00d_feed;
+/// The size of the device tree blob header fields read before parsing
+/// (`magic` and `totalsize`).
+const FDT_HEADER_PREFIX: usize = 8;
+
+/// The device tree blob passed by the SBI firmware, if it is valid.
+static FDT: LazyInit<Option<Fdt<'static>>> = LazyInit::new();
+
+static RAM_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
+static RESERVED_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
 
 struct MemIfImpl;
 
-#[allow(dead_code)]
 pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
 }
 
-#[allow(dead_code)]
 pub const fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
     pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
 }
 
+/// Returns the physical memory range where the kernel image is loaded.
+fn kernel_image_range() -> RawRange {
+    unsafe extern "C" {
+        fn _ekernel();
+    }
+    (KERNEL_BASE_PADDR, _ekernel as usize - KERNEL_BASE_VADDR)
+}
+
+/// Cuts off the part of `range` below `KERNEL_BASE_PADDR`.
+///
+/// Memory below the kernel image is occupied by the SBI firmware, which can
+/// be neither allocated nor mapped.
+fn clip_below_kernel((start, size): RawRange) -> Option<RawRange> {
+    let end = start + size;
+    let start = start.max(KERNEL_BASE_PADDR);
+    (start < end).then(|| (start, end - start))
+}
+
+/// Returns whether the physical range is mapped by the boot page table, so
+/// that it can be accessed by `phys_to_virt` at early boot.
+fn is_boot_mapped(paddr: usize, size: usize) -> bool {
+    let Some(end) = paddr.checked_add(size) else {
+        return false;
+    };
+    BOOT_MAPPED_RANGES
+        .iter()
+        .any(|&(start, len)| paddr >= start && end <= start + len)
+}
+
+/// Parses the device tree blob at `dtb_paddr`.
+///
+/// The blob is ignored if it is not mapped by the boot page table (e.g., it is
+/// placed at the top of a RAM larger than 1GiB), since it cannot be read at
+/// early boot.
+fn parse_fdt(dtb_paddr: usize) -> Option<Fdt<'static>> {
+    if dtb_paddr == 0 {
+        return None;
+    }
+    if !is_boot_mapped(dtb_paddr, FDT_HEADER_PREFIX) {
+        warn!("DTB at {dtb_paddr:#x} is not mapped at boot, ignored");
+        return None;
+    }
+    let ptr = phys_to_virt(pa!(dtb_paddr)).as_ptr();
+    let read_be32 =
+        |offset: usize| unsafe { u32::from_be((ptr.add(offset) as *const u32).read_unaligned()) };
+    let total_size = read_be32(4) as usize;
+    if read_be32(0) == FDT_MAGIC && !is_boot_mapped(dtb_paddr, total_size) {
+        warn!(
+            "DTB at {:#x?} is not mapped at boot, ignored",
+            dtb_paddr..dtb_paddr + total_size
+        );
+        return None;
+    }
+    unsafe { Fdt::from_ptr(ptr) }
+        .inspect_err(|e| warn!("Invalid DTB at {dtb_paddr:#x}: {e}"))
+        .ok()
+}
+
+/// Returns the device tree blob passed by the SBI firmware.
+///
+/// It is `None` before [`init`], or if the blob is invalid.
+#[cfg_attr(not(feature = "irq"), allow(dead_code))]
+pub(crate) fn fdt() -> Option<&'static Fdt<'static>> {
+    FDT.get()?.as_ref()
+}
+
+/// Parses the device tree blob to get the physical memory layout.
+///
+/// If the blob is invalid or not accessible, a range of `PHYS_MEMORY_SIZE`
+/// bytes starting at `KERNEL_BASE_PADDR` is used instead.
+pub(crate) fn init(dtb_paddr: usize) {
+    let mut ram = Vec::new();
+    let mut reserved: Vec<RawRange, MAX_REGIONS> = Vec::new();
+
+    if let Some(fdt) = FDT.init_once(parse_fdt(dtb_paddr)) {
+        for r in fdt.memory_regions().filter_map(clip_below_kernel) {
+            if ram.push(r).is_err() {
+                warn!("Too many RAM regions in DTB, ignore {r:#x?}");
+            }
+        }
+        for r in fdt
+            .reserved_regions_with_blob(dtb_paddr)
+            .filter_map(clip_below_kernel)
+        {
+            if reserved.push(r).is_err() {
+                warn!("Too many reserved regions in DTB, ignore {r:#x?}");
+            }
+        }
+    }
+
+    if ram.is_empty() {
+        ram.push((KERNEL_BASE_PADDR, PHYS_MEMORY_SIZE)).unwrap();
+    }
+    ram.sort_unstable_by_key(|r| r.0);
+    if cfg!(feature = "klog") && KLOG_SIZE > 0 {
+        let klog = (KLOG_PADDR, KLOG_SIZE);
+        if !ranges_contain(&ram, klog) {
+            warn!("klog range {klog:#x?} is not in RAM, ignore it");
+        } else if reserved.push(klog).is_err() {
+            warn!("Too many reserved regions, ignore klog range {klog:#x?}");
+        }
+    }
+    RAM_REGIONS.init_once(ram);
+    let mut res = Vec::new();
+    normalize_ranges(&mut reserved, &[kernel_image_range()], |r| {
+        if res.push(r).is_err() {
+            warn!("Too many reserved regions, ignore {r:#x?}");
+        }
+    });
+    RESERVED_REGIONS.init_once(res);
+}
+
 #[impl_plat_interface]
 impl MemIf for MemIfImpl {
     /// Returns all physical memory (RAM) ranges on the platform.
@@ -23,9 +155,7 @@ impl MemIf for MemIfImpl {
     /// All memory ranges except reserved ranges (including the kernel loaded
     /// range) are free for allocation.
     fn phys_ram_ranges() -> &'static [RawRange] {
-        // TODO: paser dtb to get the available memory ranges
-        // We can't directly use `PHYS_MEMORY_BASE` here, because it may has been used by sbi.
-        &[(KERNEL_BASE_PADDR, PHYS_MEMORY_SIZE)]
+        RAM_REGIONS.as_slice()
     }
 
     /// Returns all reserved physical memory ranges on the platform.
@@ -36,11 +166,26 @@ impl MemIf for MemIfImpl {
     /// Note that the ranges returned should not include the range where the
     /// kernel is loaded.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[]
+        RESERVED_REGIONS.as_slice()
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange] {
         &MMIO_RANGES
     }
+
+    /// Translates a physical address to a virtual address.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        phys_to_virt(paddr)
+    }
+
+    /// Translates a virtual address to a physical address.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+        virt_to_phys(vaddr)
+    }
+
+    /// Returns the offset between bus addresses and physical addresses.
+    fn phys_bus_offset() -> usize {
+        PHYS_BUS_OFFSET
+    }
 }
@@ -0,0 +1,95 @@
+//! NS16550 UART driven through MMIO.
+//!
+//! The console uses SBI calls at boot, and is switched to the UART at
+//! [`init_later`](axplat::init::InitIf::init_later), when the kernel page table
+//! (with the UART in `mmio-ranges`) is in use. The line settings are kept as
+//! configured by the SBI firmware.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axplat::console::ConsoleRx;
+use kspin::SpinNoIrq;
+use memory_addr::PhysAddr;
+use ns16550a::Uart;
+
+use crate::mem::phys_to_virt;
+
+const UART_BASE: PhysAddr = pa!(crate::config::devices::UART_PADDR);
+
+/// The interrupt source of the UART in the PLIC (or APLIC).
+#[cfg(feature = "irq")]
+const UART_IRQ: usize = crate::config::devices::UART_IRQ;
+
+/// Interrupt enable register.
+#[cfg(feature = "irq")]
+const IER: usize = 1;
+/// Enables the received data available interrupt.
+#[cfg(feature = "irq")]
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+
+static UART: SpinNoIrq<Uart> = SpinNoIrq::new(Uart::new(phys_to_virt(UART_BASE).as_usize()));
+
+static RX: ConsoleRx<256> = ConsoleRx::new();
+
+/// Whether the console is switched to the UART.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Switches the console to the UART.
+pub fn init() {
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Returns whether the console is switched to the UART.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Enables the UART receive interrupt, after the PLIC (or APLIC) is
+/// initialized.
+#[cfg(feature = "irq")]
+pub fn init_irq() {
+    if RX.register_irq(UART_IRQ, irq_handler) {
+        let uart = UART.lock();
+        let ier = (uart.base_address() + IER) as *mut u8;
+        unsafe { ier.write_volatile(IER_RX_AVAILABLE) };
+    } else {
+        warn!("failed to register the UART IRQ");
+    }
+}
+
+#[cfg(feature = "irq")]
+fn irq_handler() {
+    let uart = UART.lock();
+    RX.receive(|| uart.get());
+}
+
+/// Writes a byte to the UART, waiting for the transmitter to be ready.
+fn putchar(uart: &Uart, c: u8) {
+    while uart.put(c).is_none() {
+        core::hint::spin_loop();
+    }
+}
+
+/// Writes bytes to the UART.
+pub fn write_bytes(bytes: &[u8]) {
+    for &c in bytes {
+        let uart = UART.lock();
+        if c == b'\n' {
+            putchar(&uart, b'\r');
+        }
+        putchar(&uart, c);
+    }
+}
+
+/// Reads bytes from the UART into the given mutable slice.
+/// Returns the number of bytes read.
+pub fn read_bytes(bytes: &mut [u8]) -> usize {
+    let uart = UART.lock();
+    RX.read(bytes, || uart.get())
+}
+
+/// Returns the IRQ number of the UART input, or `None` if the receive
+/// interrupt is not enabled.
+pub fn irq_num() -> Option<usize> {
+    RX.irq_num()
+}
@@ -0,0 +1,109 @@
+//! Platform-Level Interrupt Controller (PLIC).
+//!
+//! All external interrupts are routed to the S-mode context of the boot hart.
+//! See the [RISC-V PLIC specification](https://github.com/riscv/riscv-plic-spec)
+//! for the register layout.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use kspin::SpinNoIrq;
+use memory_addr::PhysAddr;
+
+use crate::config::devices::PLIC_PADDR;
+use crate::mem::phys_to_virt;
+
+const PLIC_BASE: PhysAddr = pa!(PLIC_PADDR);
+
+/// The number of interrupt sources, including the reserved source 0.
+pub const PLIC_NUM_SOURCES: usize = 1024;
+
+/// Interrupt source priority registers, one word per source.
+const PRIORITY_OFFSET: usize = 0;
+/// Interrupt enable bits of context 0, one bit per source.
+const ENABLE_OFFSET: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+/// Priority threshold register of context 0, followed by the claim/complete
+/// register.
+const CONTEXT_OFFSET: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const CONTEXT_THRESHOLD: usize = 0;
+const CONTEXT_CLAIM: usize = 4;
+
+/// The priority of enabled sources. Any non-zero priority is above the
+/// threshold 0.
+const DEFAULT_PRIORITY: u32 = 1;
+
+/// The hart that external interrupts are routed to.
+static TARGET_HART: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes read-modify-write accesses to the enable registers.
+static ENABLE_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+/// Returns the S-mode context of the given hart.
+///
+/// Each hart has an M-mode and an S-mode context on QEMU virt.
+const fn s_context(hart_id: usize) -> usize {
+    2 * hart_id + 1
+}
+
+fn reg(offset: usize) -> *mut u32 {
+    (phys_to_virt(PLIC_BASE) + offset).as_mut_ptr_of()
+}
+
+fn read(offset: usize) -> u32 {
+    unsafe { reg(offset).read_volatile() }
+}
+
+fn write(offset: usize, value: u32) {
+    unsafe { reg(offset).write_volatile(value) }
+}
+
+fn context_reg(hart_id: usize, reg: usize) -> usize {
+    CONTEXT_OFFSET + s_context(hart_id) * CONTEXT_STRIDE + reg
+}
+
+/// Routes external interrupts to the given hart.
+pub fn init(boot_hart_id: usize) {
+    TARGET_HART.store(boot_hart_id, Ordering::Release);
+}
+
+/// Accepts interrupts of all priorities on the current hart.
+pub fn init_percpu(hart_id: usize) {
+    write(context_reg(hart_id, CONTEXT_THRESHOLD), 0);
+}
+
+/// Enables or disables the given interrupt source.
+pub fn set_enable(irq: usize, enabled: bool) {
+    if irq == 0 || irq >= PLIC_NUM_SOURCES {
+        warn!("invalid PLIC source {irq}");
+        return;
+    }
+    if enabled {
+        write(PRIORITY_OFFSET + irq * 4, DEFAULT_PRIORITY);
+    }
+    let offset = ENABLE_OFFSET + s_context(target_hart()) * ENABLE_STRIDE + irq / 32 * 4;
+    let bit = 1 << (irq % 32);
+    let _guard = ENABLE_LOCK.lock();
+    let value = read(offset);
+    write(offset, if enabled { value | bit } else { value & !bit });
+}
+
+/// Claims the highest priority pending interrupt of the target hart.
+///
+/// Returns `None` if there is no pending interrupt.
+pub fn claim() -> Option<usize> {
+    match read(context_reg(target_hart(), CONTEXT_CLAIM)) {
+        0 => None,
+        irq => Some(irq as usize),
+    }
+}
+
+/// Signals the completion of a claimed interrupt.
+pub fn complete(irq: usize) {
+    write(context_reg(target_hart(), CONTEXT_CLAIM), irq as u32);
+}
+
+/// Returns the hart that external interrupts are routed to.
+fn target_hart() -> usize {
+    TARGET_HART.load(Ordering::Acquire)
+}
@@ -1,4 +1,12 @@
-use axplat::power::PowerIf;
+use axplat::power::{CpuIdleKind, CpuIdleState, PowerIf};
+
+use crate::sbi::{SbiExtension, sbi_info};
+
+/// CPU idle states, the second one requires the SBI HSM extension.
+const IDLE_STATES: &[CpuIdleState] = &[
+    CpuIdleState::new("wfi", CpuIdleKind::Standby, 1, 1),
+    CpuIdleState::new("sbi-retentive", CpuIdleKind::Retention, 10, 20),
+];
 
 struct PowerImpl;
 
@@ -15,7 +23,7 @@ impl PowerIf for PowerImpl {
             unsafe extern "C" {
                 fn _start_secondary();
             }
-            if sbi_rt::probe_extension(sbi_rt::Hsm).is_unavailable() {
+            if !sbi_info().has_extension(SbiExtension::Hsm) {
                 warn!("HSM SBI extension is not supported for current SEE.");
                 return;
             }
@@ -24,6 +32,35 @@ impl PowerIf for PowerImpl {
         }
     }
 
+    /// Powers off the calling CPU core by the SBI HSM extension.
+    fn cpu_off() -> ! {
+        axcpu::asm::disable_irqs();
+        let ret = sbi_rt::hart_stop();
+        error!("failed to stop the hart ({:?})", ret.error);
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Returns all idle states supported by the calling CPU.
+    fn cpu_idle_states() -> &'static [CpuIdleState] {
+        if sbi_info().has_extension(SbiExtension::Hsm) {
+            IDLE_STATES
+        } else {
+            &IDLE_STATES[..1]
+        }
+    }
+
+    /// Puts the calling CPU into the idle state at `index`.
+    ///
+    /// The retention state is entered by the SBI HSM `hart_suspend` call with
+    /// the default retentive suspend type.
+    fn cpu_idle(index: usize) {
+        if index == 0 || sbi_rt::hart_suspend(sbi_rt::Retentive, 0, 0).is_err() {
+            axcpu::asm::wait_for_irqs();
+        }
+    }
+
     /// Shutdown the whole system.
     fn system_off() -> ! {
         info!("Shutting down...");
@@ -33,4 +70,14 @@ impl PowerIf for PowerImpl {
             axcpu::asm::halt();
         }
     }
+
+    /// Resets (reboots) the whole system.
+    fn system_reset() -> ! {
+        info!("Resetting...");
+        sbi_rt::system_reset(sbi_rt::ColdReboot, sbi_rt::NoReason);
+        warn!("It should reset!");
+        loop {
+            axcpu::asm::halt();
+        }
+    }
 }
@@ -0,0 +1,114 @@
+//! SBI implementation information.
+//!
+//! The SBI specification version and the extensions used by the platform are
+//! probed once, at [`init_early`](axplat::init::InitIf::init_early) or at the
+//! first query before that (e.g., by the early console).
+
+use lazyinit::LazyInit;
+
+/// SBI extensions used by the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbiExtension {
+    /// Timer extension (`TIME`).
+    Timer,
+    /// IPI extension (`sPI`).
+    Ipi,
+    /// Remote fence extension (`RFNC`).
+    Rfence,
+    /// Hart state management extension (`HSM`).
+    Hsm,
+    /// System reset extension (`SRST`).
+    Reset,
+    /// Debug console extension (`DBCN`).
+    Console,
+    /// System suspend extension (`SUSP`).
+    Suspend,
+}
+
+impl SbiExtension {
+    const ALL: [Self; 7] = [
+        Self::Timer,
+        Self::Ipi,
+        Self::Rfence,
+        Self::Hsm,
+        Self::Reset,
+        Self::Console,
+        Self::Suspend,
+    ];
+
+    fn probe(self) -> bool {
+        use sbi_rt::probe_extension as probe;
+        match self {
+            Self::Timer => probe(sbi_rt::Timer),
+            Self::Ipi => probe(sbi_rt::Ipi),
+            Self::Rfence => probe(sbi_rt::Fence),
+            Self::Hsm => probe(sbi_rt::Hsm),
+            Self::Reset => probe(sbi_rt::Reset),
+            Self::Console => probe(sbi_rt::Console),
+            Self::Suspend => probe(sbi_rt::Suspend),
+        }
+        .is_available()
+    }
+}
+
+/// Information about the SBI implementation.
+#[derive(Debug, Clone, Copy)]
+pub struct SbiInfo {
+    /// The major and minor version of the SBI specification.
+    pub spec_version: (usize, usize),
+    /// The SBI implementation ID (e.g., 1 for OpenSBI, 4 for RustSBI).
+    pub impl_id: usize,
+    /// The SBI implementation version, whose encoding is implementation
+    /// specific.
+    pub impl_version: usize,
+    /// Bit `i` is set if `SbiExtension::ALL[i]` is available.
+    extensions: u32,
+}
+
+impl SbiInfo {
+    /// Returns whether the given extension is available.
+    ///
+    /// It is always `false` on SBI v0.1, which provides only the legacy
+    /// extensions.
+    pub fn has_extension(&self, ext: SbiExtension) -> bool {
+        self.extensions & (1 << ext as u32) != 0
+    }
+}
+
+static SBI_INFO: LazyInit<SbiInfo> = LazyInit::new();
+
+fn probe() -> SbiInfo {
+    let version = sbi_rt::get_spec_version();
+    let spec_version = (version.major(), version.minor());
+    // The base extension (and all other non-legacy extensions) is introduced
+    // in SBI v0.2.
+    if spec_version < (0, 2) {
+        return SbiInfo {
+            spec_version: (0, 1),
+            impl_id: 0,
+            impl_version: 0,
+            extensions: 0,
+        };
+    }
+    let extensions = SbiExtension::ALL
+        .iter()
+        .filter(|ext| ext.probe())
+        .fold(0, |mask, &ext| mask | (1 << ext as u32));
+    SbiInfo {
+        spec_version,
+        impl_id: sbi_rt::get_sbi_impl_id(),
+        impl_version: sbi_rt::get_sbi_impl_version(),
+        extensions,
+    }
+}
+
+/// Returns information about the SBI implementation.
+pub fn sbi_info() -> &'static SbiInfo {
+    SBI_INFO.call_once(probe);
+    &SBI_INFO
+}
+
+/// Probes the SBI implementation.
+pub(crate) fn init_early() {
+    sbi_info();
+}
@@ -1,14 +1,86 @@
+#[cfg(feature = "irq")]
+use core::sync::atomic::{AtomicBool, Ordering};
 use riscv::register::time;
 
-use axplat::time::TimeIf;
+use axplat::time::{ClockSource, TimeIf};
 
-const NANOS_PER_SEC: u64 = 1_000_000_000;
+const CLOCK: ClockSource = ClockSource::new(crate::config::devices::TIMER_FREQUENCY as u64, 64);
 
-const NANOS_PER_TICK: u64 = NANOS_PER_SEC / crate::config::devices::TIMER_FREQUENCY as u64;
 /// RTC wall time offset in nanoseconds at monotonic time base.
 static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
 
+/// Whether timers are set by writing the `stimecmp` CSR directly (the Sstc
+/// extension), instead of SBI calls.
+///
+/// S-mode can access `stimecmp` only if the firmware sets `menvcfg.STCE`,
+/// which OpenSBI does for harts with the extension since it implements SBI
+/// v1.0. It is enabled only with such firmware, since the access would trap
+/// otherwise.
+#[cfg(feature = "irq")]
+static SSTC_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The minimum SBI specification version whose firmware is trusted to enable
+/// S-mode access to `stimecmp`.
+#[cfg(feature = "irq")]
+const SSTC_MIN_SBI_VERSION: (usize, usize) = (1, 0);
+
+/// Returns whether all harts in the DTB support the Sstc extension.
+///
+/// The extension is looked up in the `riscv,isa-extensions` property, or the
+/// multi-letter extensions of the `riscv,isa` string (e.g.,
+/// `rv64imafdc_zicsr_sstc`).
+#[cfg(feature = "irq")]
+fn dtb_has_sstc() -> bool {
+    let Some(cpus) = crate::mem::fdt().and_then(|fdt| fdt.find_node("/cpus")) else {
+        return false;
+    };
+    let mut harts = cpus
+        .children()
+        .filter(|n| n.property("device_type").and_then(|p| p.as_str()) == Some("cpu"))
+        .peekable();
+    harts.peek().is_some()
+        && harts.all(|hart| {
+            if let Some(exts) = hart.property("riscv,isa-extensions") {
+                exts.as_str_list().any(|ext| ext == "sstc")
+            } else if let Some(isa) = hart.property("riscv,isa").and_then(|p| p.as_str()) {
+                isa.split('_').skip(1).any(|ext| ext == "sstc")
+            } else {
+                false
+            }
+        })
+}
+
+/// Sets the next timer interrupt at the given deadline in ticks.
+#[cfg(feature = "irq")]
+fn set_timer(deadline_ticks: u64) {
+    if SSTC_ENABLED.load(Ordering::Relaxed) {
+        // SAFETY: `stimecmp` is accessible since the Sstc extension is present,
+        // and enabled by the firmware.
+        unsafe { core::arch::asm!("csrw 0x14d, {}", in(reg) deadline_ticks) }; // stimecmp
+    } else {
+        sbi_rt::set_timer(deadline_ticks);
+    }
+}
+
+/// Initializes the timer at the early stage.
+///
+/// It must be called after the DTB is parsed by [`crate::mem::init`], and the
+/// SBI implementation is probed by [`crate::sbi::init_early`].
 pub(super) fn init_early() {
+    #[cfg(feature = "irq")]
+    if dtb_has_sstc() {
+        let sbi_version = crate::sbi::sbi_info().spec_version;
+        if sbi_version >= SSTC_MIN_SBI_VERSION {
+            info!("Sstc extension detected, using stimecmp for timers");
+            SSTC_ENABLED.store(true, Ordering::Relaxed);
+        } else {
+            info!(
+                "Sstc extension detected, but SBI v{}.{} may not enable it, using SBI timers",
+                sbi_version.0, sbi_version.1
+            );
+        }
+    }
+
     #[cfg(feature = "rtc")]
     use crate::config::devices::RTC_PADDR;
 
@@ -33,7 +105,7 @@ pub(super) fn init_early() {
 
 pub(super) fn init_percpu() {
     #[cfg(feature = "irq")]
-    sbi_rt::set_timer(0);
+    set_timer(0);
 }
 
 struct TimeIfImpl;
@@ -47,12 +119,12 @@ impl TimeIf for TimeIfImpl {
 
     /// Converts hardware ticks to nanoseconds.
     fn ticks_to_nanos(ticks: u64) -> u64 {
-        ticks * NANOS_PER_TICK
+        CLOCK.ticks_to_nanos(ticks)
     }
 
     /// Converts nanoseconds to hardware ticks.
     fn nanos_to_ticks(nanos: u64) -> u64 {
-        nanos / NANOS_PER_TICK
+        CLOCK.nanos_to_ticks(nanos)
     }
 
     /// Return epoch offset in nanoseconds (wall time offset to monotonic clock start).
@@ -65,6 +137,6 @@ impl TimeIf for TimeIfImpl {
     /// A timer interrupt will be triggered at the specified monotonic time deadline (in nanoseconds).
     fn set_oneshot_timer(_deadline_ns: u64) {
         #[cfg(feature = "irq")]
-        sbi_rt::set_timer(Self::nanos_to_ticks(_deadline_ns));
+        set_timer(Self::nanos_to_ticks(_deadline_ns));
     }
 }
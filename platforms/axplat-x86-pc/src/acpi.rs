@@ -0,0 +1,418 @@
+//! ACPI table parsing.
+//!
+//! Only the tables needed by the platform are parsed:
+//!
+//! - MADT: local APIC IDs, IO APICs and ISA interrupt source overrides.
+//! - MCFG: PCI Express ECAM regions.
+//! - FADT (and `\_S5` in the DSDT): PM1 control blocks and reset register.
+//! - HPET: HPET base address.
+
+use heapless::Vec;
+use lazyinit::LazyInit;
+use memory_addr::PhysAddr;
+
+use crate::mem::phys_to_virt;
+
+/// The maximum number of local APICs recorded from the MADT.
+///
+/// It is larger than 256, since there can be more CPUs with x2APIC.
+pub const MAX_LOCAL_APICS: usize = 1024;
+/// The maximum number of IO APICs recorded from the MADT.
+pub const MAX_IO_APICS: usize = 8;
+/// The maximum number of interrupt source overrides recorded from the MADT.
+pub const MAX_IRQ_OVERRIDES: usize = 16;
+/// The maximum number of ECAM regions recorded from the MCFG.
+pub const MAX_PCI_ECAMS: usize = 4;
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+/// The size of the SDT header.
+const SDT_HEADER_SIZE: usize = 36;
+
+/// An IO APIC described by the MADT.
+#[derive(Debug, Clone, Copy)]
+pub struct IoApicInfo {
+    /// The IO APIC ID.
+    pub id: u8,
+    /// The physical address of the IO APIC registers.
+    pub paddr: PhysAddr,
+    /// The first global system interrupt (GSI) handled by the IO APIC.
+    pub gsi_base: u32,
+}
+
+/// An ISA interrupt source override described by the MADT.
+#[derive(Debug, Clone, Copy)]
+pub struct IrqOverride {
+    /// The ISA IRQ number.
+    pub irq: u8,
+    /// The global system interrupt (GSI) the ISA IRQ is connected to.
+    pub gsi: u32,
+    /// The MPS INTI flags (polarity in bits 0-1, trigger mode in bits 2-3).
+    pub flags: u16,
+}
+
+impl IrqOverride {
+    /// Returns whether the interrupt is level-triggered. ISA IRQs conform to
+    /// the bus specification (edge-triggered) by default.
+    pub const fn is_level_triggered(&self) -> bool {
+        (self.flags >> 2) & 0b11 == 0b11
+    }
+
+    /// Returns whether the interrupt is active low. ISA IRQs conform to the
+    /// bus specification (active high) by default.
+    pub const fn is_active_low(&self) -> bool {
+        self.flags & 0b11 == 0b11
+    }
+}
+
+/// A PCI Express ECAM region described by the MCFG.
+#[derive(Debug, Clone, Copy)]
+pub struct PciEcam {
+    /// The physical base address of the ECAM region.
+    pub paddr: PhysAddr,
+    /// The PCI segment group number.
+    pub segment: u16,
+    /// The first bus number decoded by the region.
+    pub bus_start: u8,
+    /// The last bus number decoded by the region.
+    pub bus_end: u8,
+}
+
+/// An ACPI generic address structure (GAS).
+#[derive(Debug, Clone, Copy)]
+pub struct GenericAddress {
+    /// The address space (0: system memory, 1: system IO, 2: PCI config).
+    pub space_id: u8,
+    /// The size of the register in bits.
+    pub bit_width: u8,
+    /// The bit offset of the register at the address.
+    pub bit_offset: u8,
+    /// The access size.
+    pub access_size: u8,
+    /// The address in the given address space.
+    pub address: u64,
+}
+
+impl GenericAddress {
+    /// System memory address space.
+    pub const SPACE_MEMORY: u8 = 0;
+    /// System IO address space.
+    pub const SPACE_IO: u8 = 1;
+}
+
+/// Platform information collected from the ACPI tables.
+#[derive(Debug, Default)]
+pub struct AcpiInfo {
+    /// The physical address of the local APIC registers.
+    pub local_apic_paddr: Option<PhysAddr>,
+    /// The APIC IDs of usable processors, the BSP is usually the first one.
+    pub local_apic_ids: Vec<u32, MAX_LOCAL_APICS>,
+    /// The IO APICs.
+    pub io_apics: Vec<IoApicInfo, MAX_IO_APICS>,
+    /// The ISA interrupt source overrides.
+    pub irq_overrides: Vec<IrqOverride, MAX_IRQ_OVERRIDES>,
+    /// The PCI Express ECAM regions.
+    pub pci_ecams: Vec<PciEcam, MAX_PCI_ECAMS>,
+    /// The SMI command port, used to enable ACPI mode.
+    pub smi_cmd_port: u16,
+    /// The value written to the SMI command port to enable ACPI mode.
+    pub acpi_enable: u8,
+    /// The IO port of the PM1a control block.
+    pub pm1a_cnt_port: Option<u16>,
+    /// The IO port of the PM1b control block.
+    pub pm1b_cnt_port: Option<u16>,
+    /// The `SLP_TYPa` and `SLP_TYPb` values of the S5 (soft off) state.
+    pub s5_sleep_types: Option<(u8, u8)>,
+    /// The reset register and the value to write to it.
+    pub reset_reg: Option<(GenericAddress, u8)>,
+    /// The physical base address of the HPET.
+    pub hpet_paddr: Option<PhysAddr>,
+}
+
+static ACPI_INFO: LazyInit<AcpiInfo> = LazyInit::new();
+
+/// Returns the information collected from the ACPI tables, or `None` if they
+/// are not found.
+pub fn info() -> Option<&'static AcpiInfo> {
+    ACPI_INFO.is_inited().then(|| &*ACPI_INFO)
+}
+
+/// Returns the global system interrupt (GSI) the given ISA IRQ is connected
+/// to.
+///
+/// ISA IRQs are identity-mapped to GSIs unless overridden by the MADT.
+pub fn isa_irq_to_gsi(irq: u8) -> u32 {
+    info()
+        .and_then(|info| info.irq_overrides.iter().find(|o| o.irq == irq))
+        .map_or(irq as u32, |o| o.gsi)
+}
+
+fn phys_bytes(paddr: usize, len: usize) -> &'static [u8] {
+    let ptr = phys_to_virt(pa!(paddr)).as_ptr();
+    unsafe { core::slice::from_raw_parts(ptr, len) }
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Option<u8> {
+    bytes.get(offset).copied()
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn read_gas(bytes: &[u8], offset: usize) -> Option<GenericAddress> {
+    Some(GenericAddress {
+        space_id: read_u8(bytes, offset)?,
+        bit_width: read_u8(bytes, offset + 1)?,
+        bit_offset: read_u8(bytes, offset + 2)?,
+        access_size: read_u8(bytes, offset + 3)?,
+        address: read_u64(bytes, offset + 4)?,
+    })
+}
+
+fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+/// Returns the system description table at the given physical address if its
+/// checksum is valid.
+fn sdt_at(paddr: usize) -> Option<&'static [u8]> {
+    if paddr == 0 {
+        return None;
+    }
+    let len = read_u32(phys_bytes(paddr, SDT_HEADER_SIZE), 4)? as usize;
+    if len < SDT_HEADER_SIZE {
+        return None;
+    }
+    let table = phys_bytes(paddr, len);
+    checksum_ok(table).then_some(table)
+}
+
+/// Searches the RSDP in the first KiB of the EBDA and the BIOS read-only
+/// memory area `0xe0000..0x100000`.
+fn find_rsdp() -> Option<usize> {
+    let ebda = (read_u16(phys_bytes(0x40e, 2), 0)? as usize) << 4;
+    let areas = [(ebda, 0x400), (0xe0000, 0x20000)];
+    areas
+        .into_iter()
+        .filter(|&(base, _)| base != 0)
+        .flat_map(|(base, size)| (base..base + size).step_by(16))
+        .find(|&paddr| {
+            let rsdp = phys_bytes(paddr, 20);
+            rsdp.starts_with(RSDP_SIGNATURE) && checksum_ok(rsdp)
+        })
+}
+
+/// Returns the physical addresses of all tables listed in the XSDT (or the
+/// RSDT for ACPI 1.0).
+fn table_addrs(rsdp_paddr: usize) -> impl Iterator<Item = usize> {
+    let rsdp = phys_bytes(rsdp_paddr, 36);
+    let revision = rsdp[15];
+    let (sdt, entry_size) = match read_u64(rsdp, 24) {
+        Some(xsdt) if revision >= 2 && xsdt != 0 => (sdt_at(xsdt as usize), 8),
+        _ => (sdt_at(read_u32(rsdp, 16).unwrap_or(0) as usize), 4),
+    };
+    let entries = sdt.map_or(&[][..], |sdt| &sdt[SDT_HEADER_SIZE..]);
+    entries
+        .chunks_exact(entry_size)
+        .map(move |entry| match entry_size {
+            8 => read_u64(entry, 0).unwrap_or(0) as usize,
+            _ => read_u32(entry, 0).unwrap_or(0) as usize,
+        })
+}
+
+fn parse_madt(madt: &[u8], info: &mut AcpiInfo) {
+    const LOCAL_APIC: u8 = 0;
+    const IO_APIC: u8 = 1;
+    const INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+    const LOCAL_X2APIC: u8 = 9;
+    /// The processor is enabled.
+    const ENABLED: u32 = 1 << 0;
+
+    info.local_apic_paddr = read_u32(madt, 36).map(|paddr| pa!(paddr as usize));
+    let mut offset = SDT_HEADER_SIZE + 8;
+    while let (Some(ty), Some(len)) = (read_u8(madt, offset), read_u8(madt, offset + 1)) {
+        let len = len as usize;
+        if len < 2 {
+            break;
+        }
+        let entry = madt.get(offset..offset + len).unwrap_or_default();
+        match ty {
+            LOCAL_APIC | LOCAL_X2APIC => {
+                let (apic_id, flags) = if ty == LOCAL_APIC {
+                    (read_u8(entry, 3).map(u32::from), read_u32(entry, 4))
+                } else {
+                    (read_u32(entry, 4), read_u32(entry, 8))
+                };
+                if let (Some(apic_id), Some(flags)) = (apic_id, flags)
+                    && flags & ENABLED != 0
+                    && !info.local_apic_ids.contains(&apic_id)
+                    && info.local_apic_ids.push(apic_id).is_err()
+                {
+                    warn!("too many local APICs in MADT, ignoring APIC ID {apic_id}");
+                }
+            }
+            IO_APIC => {
+                if let (Some(id), Some(paddr), Some(gsi_base)) =
+                    (read_u8(entry, 2), read_u32(entry, 4), read_u32(entry, 8))
+                {
+                    let paddr = pa!(paddr as usize);
+                    let io_apic = IoApicInfo {
+                        id,
+                        paddr,
+                        gsi_base,
+                    };
+                    if info.io_apics.push(io_apic).is_err() {
+                        warn!("too many IO APICs in MADT, ignoring IO APIC {id}");
+                    }
+                }
+            }
+            INTERRUPT_SOURCE_OVERRIDE => {
+                if let (Some(irq), Some(gsi), Some(flags)) =
+                    (read_u8(entry, 3), read_u32(entry, 4), read_u16(entry, 8))
+                    && info
+                        .irq_overrides
+                        .push(IrqOverride { irq, gsi, flags })
+                        .is_err()
+                {
+                    warn!("too many interrupt source overrides in MADT, ignoring IRQ {irq}");
+                }
+            }
+            _ => {}
+        }
+        offset += len;
+    }
+}
+
+fn parse_mcfg(mcfg: &[u8], info: &mut AcpiInfo) {
+    let entries = mcfg.get(SDT_HEADER_SIZE + 8..).unwrap_or_default();
+    for entry in entries.chunks_exact(16) {
+        if let (Some(paddr), Some(segment), Some(bus_start), Some(bus_end)) = (
+            read_u64(entry, 0),
+            read_u16(entry, 8),
+            read_u8(entry, 10),
+            read_u8(entry, 11),
+        ) {
+            let _ = info.pci_ecams.push(PciEcam {
+                paddr: pa!(paddr as usize),
+                segment,
+                bus_start,
+                bus_end,
+            });
+        }
+    }
+}
+
+/// Finds the `SLP_TYPa` and `SLP_TYPb` values in the `\_S5` package of the
+/// DSDT, without a full AML interpreter.
+fn parse_s5(dsdt: &[u8]) -> Option<(u8, u8)> {
+    const NAME_OP: u8 = 0x08;
+    const BYTE_PREFIX: u8 = 0x0a;
+
+    // `_S5_` followed by `PackageOp`
+    let pos = dsdt.windows(5).position(|w| w == b"_S5_\x12")?;
+    // `Name(_S5_, ...)` or `Name(\_S5_, ...)`
+    let before = dsdt.get(pos.checked_sub(2)?..pos)?;
+    if before[1] != NAME_OP && !(before[0] == NAME_OP && before[1] == b'\\') {
+        return None;
+    }
+    // skip the package length encoding and the number of elements
+    let pkg_len_bytes = ((*dsdt.get(pos + 5)? >> 6) & 0x3) as usize;
+    let mut offset = pos + 5 + pkg_len_bytes + 2;
+    let mut next_value = || {
+        let mut value = *dsdt.get(offset)?;
+        offset += 1;
+        if value == BYTE_PREFIX {
+            value = *dsdt.get(offset)?;
+            offset += 1;
+        }
+        Some(value)
+    };
+    Some((next_value()?, next_value()?))
+}
+
+fn parse_fadt(fadt: &[u8], info: &mut AcpiInfo) {
+    /// The reset register is supported.
+    const RESET_REG_SUP: u32 = 1 << 10;
+
+    info.smi_cmd_port = read_u32(fadt, 48).unwrap_or(0) as u16;
+    info.acpi_enable = read_u8(fadt, 52).unwrap_or(0);
+
+    let io_port = |legacy_offset: usize, gas_offset: usize| {
+        let port = match read_gas(fadt, gas_offset) {
+            Some(gas) if gas.address != 0 && gas.space_id == GenericAddress::SPACE_IO => {
+                gas.address as u32
+            }
+            _ => read_u32(fadt, legacy_offset)?,
+        };
+        (port != 0).then_some(port as u16)
+    };
+    info.pm1a_cnt_port = io_port(64, 172);
+    info.pm1b_cnt_port = io_port(68, 184);
+
+    if let (Some(flags), Some(reg), Some(value)) =
+        (read_u32(fadt, 112), read_gas(fadt, 116), read_u8(fadt, 128))
+        && flags & RESET_REG_SUP != 0
+        && reg.address != 0
+    {
+        info.reset_reg = Some((reg, value));
+    }
+
+    let dsdt_paddr = match read_u64(fadt, 140) {
+        Some(x_dsdt) if x_dsdt != 0 => x_dsdt as usize,
+        _ => read_u32(fadt, 40).unwrap_or(0) as usize,
+    };
+    info.s5_sleep_types = sdt_at(dsdt_paddr).and_then(parse_s5);
+}
+
+fn parse_hpet(hpet: &[u8], info: &mut AcpiInfo) {
+    if let Some(gas) = read_gas(hpet, 40)
+        && gas.space_id == GenericAddress::SPACE_MEMORY
+        && gas.address != 0
+    {
+        info.hpet_paddr = Some(pa!(gas.address as usize));
+    }
+}
+
+/// Parses the ACPI tables.
+///
+/// The RSDP is searched in the BIOS memory areas if `rsdp_paddr` is `None`.
+/// Nothing is recorded if the RSDP is not found.
+pub(crate) fn init(rsdp_paddr: Option<PhysAddr>) {
+    let Some(rsdp_paddr) = rsdp_paddr.map(PhysAddr::as_usize).or_else(find_rsdp) else {
+        warn!("ACPI RSDP not found");
+        return;
+    };
+
+    let mut info = AcpiInfo::default();
+    for table in table_addrs(rsdp_paddr).filter_map(sdt_at) {
+        match &table[..4] {
+            b"APIC" => parse_madt(table, &mut info),
+            b"MCFG" => parse_mcfg(table, &mut info),
+            b"FACP" => parse_fadt(table, &mut info),
+            b"HPET" => parse_hpet(table, &mut info),
+            _ => {}
+        }
+    }
+    info!(
+        "ACPI: {} CPUs, {} IO APICs, {} PCI ECAM regions",
+        info.local_apic_ids.len(),
+        info.io_apics.len(),
+        info.pci_ecams.len()
+    );
+    debug!("ACPI: {info:#x?}");
+    ACPI_INFO.init_once(info);
+}
@@ -2,10 +2,13 @@
 
 use core::{cell::SyncUnsafeCell, mem::MaybeUninit};
 
+use heapless::Vec;
 use kspin::SpinNoIrq;
 use lazyinit::LazyInit;
 use memory_addr::PhysAddr;
 use x2apic::ioapic::IoApic;
+#[cfg(feature = "irq")]
+use x2apic::ioapic::{IrqFlags, IrqMode, RedirectionTableEntry};
 use x2apic::lapic::{LocalApic, LocalApicBuilder, xapic_base};
 use x86_64::instructions::port::Port;
 
@@ -13,48 +16,320 @@ use self::vectors::*;
 use crate::mem::phys_to_virt;
 
 pub(super) mod vectors {
+    /// The first vector allocated to IO APIC interrupts. Vectors from it up
+    /// to `APIC_TIMER_VECTOR` are allocated to GSIs on demand.
+    pub const IOAPIC_VECTOR_BASE: u8 = 0x20;
     pub const APIC_TIMER_VECTOR: u8 = 0xf0;
     pub const APIC_SPURIOUS_VECTOR: u8 = 0xf1;
     pub const APIC_ERROR_VECTOR: u8 = 0xf2;
+    #[cfg(feature = "irq")]
+    pub const APIC_IPI_VECTOR: u8 = 0xf3;
 }
 
+/// The default IO APIC base, if it is not described by the ACPI MADT.
 const IO_APIC_BASE: PhysAddr = pa!(0xFEC0_0000);
 
+/// The maximum number of CPUs in the logical CPU ID table.
+const MAX_CPUS: usize = crate::acpi::MAX_LOCAL_APICS;
+
+/// The number of vectors that can be allocated to GSIs.
+const NUM_GSI_VECTORS: usize = (APIC_TIMER_VECTOR - IOAPIC_VECTOR_BASE) as usize;
+
 static LOCAL_APIC: SyncUnsafeCell<MaybeUninit<LocalApic>> =
     SyncUnsafeCell::new(MaybeUninit::uninit());
 static mut IS_X2APIC: bool = false;
-static IO_APIC: LazyInit<SpinNoIrq<IoApic>> = LazyInit::new();
+static IO_APICS: LazyInit<SpinNoIrq<IoApics>> = LazyInit::new();
+
+/// The APIC IDs of all CPUs, indexed by the logical CPU IDs. The BSP is always
+/// logical CPU 0.
+static CPU_APIC_IDS: LazyInit<Vec<u32, MAX_CPUS>> = LazyInit::new();
+
+/// The trigger mode of an IO APIC interrupt.
+#[cfg(feature = "irq")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// Edge-triggered, used by ISA devices.
+    Edge,
+    /// Level-triggered, used by PCI INTx interrupts.
+    Level,
+}
+
+/// The polarity of an IO APIC interrupt.
+#[cfg(feature = "irq")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    /// Active high, used by ISA devices.
+    ActiveHigh,
+    /// Active low, used by PCI INTx interrupts.
+    ActiveLow,
+}
+
+#[cfg_attr(not(feature = "irq"), allow(dead_code))]
+struct IoApicChip {
+    regs: IoApic,
+    /// The first GSI handled by the IO APIC.
+    gsi_base: u32,
+    num_pins: u32,
+}
+
+/// All IO APICs and the GSIs that vectors are allocated to.
+#[cfg_attr(not(feature = "irq"), allow(dead_code))]
+struct IoApics {
+    chips: Vec<IoApicChip, { crate::acpi::MAX_IO_APICS }>,
+    /// The GSI of each vector from `IOAPIC_VECTOR_BASE`.
+    vector_gsi: [Option<u32>; NUM_GSI_VECTORS],
+    /// The APIC ID of the BSP, the default destination of interrupts.
+    bsp_apic_id: u8,
+}
+
+#[cfg(feature = "irq")]
+impl IoApics {
+    /// Returns the IO APIC and its pin the given GSI is connected to.
+    fn pin(&mut self, gsi: u32) -> Option<(&mut IoApic, u8)> {
+        self.chips
+            .iter_mut()
+            .find(|chip| (chip.gsi_base..chip.gsi_base + chip.num_pins).contains(&gsi))
+            .map(|chip| (&mut chip.regs, (gsi - chip.gsi_base) as u8))
+    }
+
+    fn vector_to_gsi(&self, vector: usize) -> Option<u32> {
+        let index = vector.checked_sub(IOAPIC_VECTOR_BASE as usize)?;
+        self.vector_gsi.get(index).copied().flatten()
+    }
+
+    fn gsi_to_vector(&self, gsi: u32) -> Option<usize> {
+        self.vector_gsi
+            .iter()
+            .position(|&g| g == Some(gsi))
+            .map(|index| IOAPIC_VECTOR_BASE as usize + index)
+    }
+
+    /// Programs the redirection entry of the given GSI, keeping its mask bit.
+    ///
+    /// Returns `false` if the GSI is not handled by any IO APIC.
+    fn program(
+        &mut self,
+        gsi: u32,
+        vector: u8,
+        trigger: TriggerMode,
+        polarity: Polarity,
+        dest: u8,
+    ) -> bool {
+        let Some((regs, pin)) = self.pin(gsi) else {
+            return false;
+        };
+        let mut flags = unsafe { regs.table_entry(pin) }.flags() & IrqFlags::MASKED;
+        if trigger == TriggerMode::Level {
+            flags |= IrqFlags::LEVEL_TRIGGERED;
+        }
+        if polarity == Polarity::ActiveLow {
+            flags |= IrqFlags::LOW_ACTIVE;
+        }
+        let mut entry = RedirectionTableEntry::default();
+        entry.set_vector(vector);
+        entry.set_mode(IrqMode::Fixed);
+        entry.set_flags(flags);
+        entry.set_dest(dest);
+        unsafe { regs.set_table_entry(pin, entry) };
+        true
+    }
+}
 
 /// Enables or disables the given IRQ.
 #[cfg(feature = "irq")]
 pub fn set_enable(vector: usize, enabled: bool) {
     // should not affect LAPIC interrupts
-    if vector < APIC_TIMER_VECTOR as _ {
+    let mut io_apics = IO_APICS.lock();
+    let Some(gsi) = io_apics.vector_to_gsi(vector) else {
+        return;
+    };
+    if let Some((regs, pin)) = io_apics.pin(gsi) {
         unsafe {
             if enabled {
-                IO_APIC.lock().enable_irq(vector as u8);
+                regs.enable_irq(pin);
             } else {
-                IO_APIC.lock().disable_irq(vector as u8);
+                regs.disable_irq(pin);
             }
         }
     }
 }
 
+/// Allocates a vector for the given global system interrupt (GSI), and
+/// programs its IO APIC redirection entry with the given trigger mode and
+/// polarity, targeting the BSP.
+///
+/// ISA IRQs should be mapped by [`map_isa_irq`] instead, which applies the
+/// ACPI interrupt source overrides. PCI INTx interrupts are usually
+/// level-triggered and active low.
+///
+/// The interrupt is masked until a handler is registered by
+/// [`axplat::irq::register`] with the returned vector. If the GSI is already
+/// mapped, its vector is returned and the entry is not changed.
+///
+/// Returns `None` if the GSI is not handled by any IO APIC or there are no
+/// free vectors.
+#[cfg(feature = "irq")]
+pub fn map_gsi(gsi: u32, trigger: TriggerMode, polarity: Polarity) -> Option<usize> {
+    let mut io_apics = IO_APICS.lock();
+    if let Some(vector) = io_apics.gsi_to_vector(gsi) {
+        return Some(vector);
+    }
+    io_apics.pin(gsi)?;
+    let index = io_apics.vector_gsi.iter().position(Option::is_none)?;
+    let vector = IOAPIC_VECTOR_BASE + index as u8;
+    let dest = io_apics.bsp_apic_id;
+    io_apics.program(gsi, vector, trigger, polarity, dest);
+    io_apics.vector_gsi[index] = Some(gsi);
+    debug!("GSI {gsi} ({trigger:?}, {polarity:?}) is mapped to vector {vector:#x}");
+    Some(vector as usize)
+}
+
+/// Allocates a vector for the given ISA IRQ.
+///
+/// The GSI, trigger mode and polarity are taken from the ACPI interrupt
+/// source override of the IRQ if any, otherwise the IRQ is identity-mapped to
+/// a GSI as an edge-triggered, active-high interrupt. See [`map_gsi`] for
+/// details.
+#[cfg(feature = "irq")]
+pub fn map_isa_irq(irq: u8) -> Option<usize> {
+    let over =
+        crate::acpi::info().and_then(|info| info.irq_overrides.iter().find(|o| o.irq == irq));
+    let (gsi, trigger, polarity) = match over {
+        Some(o) => (
+            o.gsi,
+            if o.is_level_triggered() {
+                TriggerMode::Level
+            } else {
+                TriggerMode::Edge
+            },
+            if o.is_active_low() {
+                Polarity::ActiveLow
+            } else {
+                Polarity::ActiveHigh
+            },
+        ),
+        None => (irq as u32, TriggerMode::Edge, Polarity::ActiveHigh),
+    };
+    map_gsi(gsi, trigger, polarity)
+}
+
+/// Routes the interrupt of the given vector (allocated by [`map_gsi`]) to the
+/// CPU with the given APIC ID.
+///
+/// Returns `false` if the vector is not allocated.
+#[cfg(feature = "irq")]
+pub fn set_irq_target(vector: usize, apic_id: u8) -> bool {
+    let mut io_apics = IO_APICS.lock();
+    let Some(gsi) = io_apics.vector_to_gsi(vector) else {
+        return false;
+    };
+    let Some((regs, pin)) = io_apics.pin(gsi) else {
+        return false;
+    };
+    unsafe {
+        let mut entry = regs.table_entry(pin);
+        entry.set_dest(apic_id);
+        regs.set_table_entry(pin, entry);
+    }
+    true
+}
+
+/// Routes the given GSI to the given vector on the BSP, as an edge-triggered,
+/// active-high interrupt, and unmasks it.
+///
+/// The vector is not allocated, so it should be one of the fixed LAPIC
+/// vectors.
+#[cfg(feature = "irq")]
+pub fn route_irq(gsi: u32, vector: u8) {
+    let mut io_apics = IO_APICS.lock();
+    let dest = io_apics.bsp_apic_id;
+    io_apics.program(gsi, vector, TriggerMode::Edge, Polarity::ActiveHigh, dest);
+    if let Some((regs, pin)) = io_apics.pin(gsi) {
+        unsafe { regs.enable_irq(pin) };
+    }
+}
+
+/// Sends an IPI with the given vector to the given logical CPU.
+#[cfg(feature = "irq")]
+pub fn send_ipi_to(cpu_id: usize, vector: u8) {
+    if let Some(apic_id) = cpu_apic_id(cpu_id) {
+        unsafe { local_apic().send_ipi(vector, raw_apic_id(apic_id)) };
+    }
+}
+
 #[cfg(any(feature = "smp", feature = "irq"))]
 pub fn local_apic<'a>() -> &'a mut LocalApic {
     // It's safe as `LOCAL_APIC` is initialized in `init_primary`.
     unsafe { LOCAL_APIC.get().as_mut().unwrap().assume_init_mut() }
 }
 
-#[cfg(feature = "smp")]
-pub fn raw_apic_id(id_u8: u8) -> u32 {
+/// Converts an APIC ID to the destination format of the ICR.
+#[cfg(any(feature = "smp", feature = "irq"))]
+pub fn raw_apic_id(apic_id: u32) -> u32 {
     if unsafe { IS_X2APIC } {
-        id_u8 as u32
+        apic_id
     } else {
-        (id_u8 as u32) << 24
+        apic_id << 24
     }
 }
 
+/// Returns the (x2)APIC ID of the current CPU.
+pub fn current_apic_id() -> u32 {
+    let cpuid = raw_cpuid::CpuId::new();
+    // the 32-bit x2APIC ID from leaf 0xB, or the 8-bit initial APIC ID
+    cpuid
+        .get_extended_topology_info()
+        .and_then(|mut levels| levels.next())
+        .map(|level| level.x2apic_id())
+        .or_else(|| {
+            cpuid
+                .get_feature_info()
+                .map(|finfo| finfo.initial_local_apic_id() as u32)
+        })
+        .unwrap_or(0)
+}
+
+/// Builds the logical CPU ID table on the BSP.
+///
+/// The APIC IDs are taken from the ACPI MADT, or assumed to be
+/// `0..CPU_NUM` if it is not found. The BSP is assigned logical CPU 0, and
+/// the others are numbered in the MADT order.
+pub fn init_cpu_ids() {
+    let bsp_apic_id = current_apic_id();
+    let mut ids = Vec::new();
+    let _ = ids.push(bsp_apic_id);
+    let push = |apic_id: u32| {
+        if apic_id != bsp_apic_id && ids.push(apic_id).is_err() {
+            warn!("too many CPUs, ignoring APIC ID {apic_id}");
+        }
+    };
+    match crate::acpi::info().filter(|info| !info.local_apic_ids.is_empty()) {
+        Some(info) => info.local_apic_ids.iter().copied().for_each(push),
+        None => (0..crate::config::plat::CPU_NUM as u32).for_each(push),
+    }
+    debug!("CPU APIC IDs: {ids:?}");
+    CPU_APIC_IDS.init_once(ids);
+}
+
+/// Returns the APIC ID of the given logical CPU, or `None` if the CPU does
+/// not exist.
+pub fn cpu_apic_id(cpu_id: usize) -> Option<u32> {
+    CPU_APIC_IDS.get()?.get(cpu_id).copied()
+}
+
+/// Returns the number of CPUs in the logical CPU ID table, which is 1 before
+/// the table is built.
+pub fn cpu_count() -> usize {
+    CPU_APIC_IDS.get().map_or(1, |ids| ids.len())
+}
+
+/// Returns the logical CPU ID of the current CPU, or `None` if its APIC ID is
+/// not in the logical CPU ID table.
+pub fn current_cpu_id() -> Option<usize> {
+    let apic_id = current_apic_id();
+    CPU_APIC_IDS.get()?.iter().position(|&id| id == apic_id)
+}
+
 fn cpu_has_x2apic() -> bool {
     match raw_cpuid::CpuId::new().get_feature_info() {
         Some(finfo) => finfo.has_x2apic(),
@@ -93,8 +368,31 @@ pub fn init_primary() {
     }
 
     info!("Initialize IO APIC...");
-    let io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
-    IO_APIC.init_once(SpinNoIrq::new(io_apic));
+    let mut chips = Vec::new();
+    let mut add_chip = |paddr: PhysAddr, gsi_base: u32| {
+        let mut regs = unsafe { IoApic::new(phys_to_virt(paddr).as_usize() as u64) };
+        // all pins are masked until their handlers are registered
+        unsafe { regs.init(IOAPIC_VECTOR_BASE) };
+        let num_pins = unsafe { regs.max_table_entry() } as u32 + 1;
+        let _ = chips.push(IoApicChip {
+            regs,
+            gsi_base,
+            num_pins,
+        });
+    };
+    match crate::acpi::info().filter(|info| !info.io_apics.is_empty()) {
+        Some(info) => {
+            for io_apic in &info.io_apics {
+                add_chip(io_apic.paddr, io_apic.gsi_base);
+            }
+        }
+        None => add_chip(IO_APIC_BASE, 0),
+    }
+    IO_APICS.init_once(SpinNoIrq::new(IoApics {
+        chips,
+        vector_gsi: [None; NUM_GSI_VECTORS],
+        bsp_apic_id: current_apic_id() as u8,
+    }));
 }
 
 #[cfg(feature = "smp")]
@@ -104,7 +402,10 @@ pub fn init_secondary() {
 
 #[cfg(feature = "irq")]
 mod irq_impl {
-    use axplat::irq::{HandlerTable, IrqHandler, IrqIf};
+    use axplat::irq::{HandlerTable, IpiIf, IpiTarget, IrqHandler, IrqIf};
+    use x2apic::lapic::IpiAllShorthand;
+
+    use super::vectors::{APIC_IPI_VECTOR, APIC_TIMER_VECTOR};
 
     /// The maximum number of IRQs.
     const MAX_IRQ_COUNT: usize = 256;
@@ -149,10 +450,40 @@ mod irq_impl {
         /// also acknowledges the interrupt controller after handling.
         fn handle(vector: usize) {
             trace!("IRQ {}", vector);
-            if !IRQ_HANDLER_TABLE.handle(vector) {
+            if vector == APIC_TIMER_VECTOR as usize && crate::time::rearm_oneshot_timer() {
+                // fired before the deadline and has been re-armed
+            } else if !IRQ_HANDLER_TABLE.handle(vector) {
                 warn!("Unhandled IRQ {}", vector);
             }
             unsafe { super::local_apic().end_of_interrupt() };
         }
     }
+
+    struct IpiIfImpl;
+
+    #[impl_plat_interface]
+    impl IpiIf for IpiIfImpl {
+        /// Returns the IRQ number of IPIs.
+        fn ipi_irq_num() -> usize {
+            APIC_IPI_VECTOR as usize
+        }
+
+        /// Sends an IPI to the given target CPU(s).
+        ///
+        /// Logical CPU IDs are translated to the local APIC IDs.
+        fn send_ipi(target: IpiTarget) {
+            let lapic = super::local_apic();
+            unsafe {
+                match target {
+                    IpiTarget::Cpu { cpu_id } => super::send_ipi_to(cpu_id, APIC_IPI_VECTOR),
+                    IpiTarget::AllExceptCurrent { .. } => {
+                        lapic.send_ipi_all(APIC_IPI_VECTOR, IpiAllShorthand::AllExcludingSelf)
+                    }
+                    IpiTarget::All { .. } => {
+                        lapic.send_ipi_all(APIC_IPI_VECTOR, IpiAllShorthand::AllIncludingSelf)
+                    }
+                }
+            }
+        }
+    }
 }
@@ -1,4 +1,4 @@
-//! Kernel booting using multiboot header.
+//! Kernel booting using multiboot (or multiboot2) header, or the PVH ELF note.
 
 use core::arch::global_asm;
 
@@ -18,6 +18,47 @@ const MULTIBOOT_HEADER_MAGIC: usize = 0x1BADB002;
 /// This should be in EAX.
 pub(super) const MULTIBOOT_BOOTLOADER_MAGIC: usize = 0x2BADB002;
 
+/// The magic field of the multiboot2 header should contain this.
+const MULTIBOOT2_HEADER_MAGIC: usize = 0xE85250D6;
+
+/// This should be in EAX if booted by a multiboot2 bootloader.
+const MULTIBOOT2_BOOTLOADER_MAGIC: usize = 0x36D76289;
+
+/// The type of the ELF note holding the 32-bit PVH entry address.
+const XEN_ELFNOTE_PHYS32_ENTRY: usize = 18;
+
+/// The magic field of `hvm_start_info`, also passed in EDI by the PVH entry.
+pub(crate) const XEN_HVM_START_MAGIC_VALUE: usize = 0x336EC578;
+
+/// The boot protocol used by the bootloader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BootProtocol {
+    Multiboot,
+    Multiboot2,
+    Pvh,
+}
+
+static mut BOOT_PROTOCOL: BootProtocol = BootProtocol::Multiboot;
+
+/// Records the boot protocol from the magic value passed by the bootloader.
+///
+/// Returns `false` if the magic value is unknown.
+pub(super) fn set_boot_protocol(magic: usize) -> bool {
+    let protocol = match magic {
+        MULTIBOOT_BOOTLOADER_MAGIC => BootProtocol::Multiboot,
+        MULTIBOOT2_BOOTLOADER_MAGIC => BootProtocol::Multiboot2,
+        XEN_HVM_START_MAGIC_VALUE => BootProtocol::Pvh,
+        _ => return false,
+    };
+    unsafe { BOOT_PROTOCOL = protocol };
+    true
+}
+
+/// Returns the boot protocol used by the bootloader.
+pub(crate) fn boot_protocol() -> BootProtocol {
+    unsafe { BOOT_PROTOCOL }
+}
+
 const CR0: u64 = Cr0Flags::PROTECTED_MODE_ENABLE.bits()
     | Cr0Flags::MONITOR_COPROCESSOR.bits()
     | Cr0Flags::NUMERIC_ERROR.bits()
@@ -40,6 +81,9 @@ global_asm!(
     mb_magic = const MULTIBOOT_BOOTLOADER_MAGIC,
     mb_hdr_magic = const MULTIBOOT_HEADER_MAGIC,
     mb_hdr_flags = const MULTIBOOT_HEADER_FLAGS,
+    mb2_hdr_magic = const MULTIBOOT2_HEADER_MAGIC,
+    pvh_magic = const XEN_HVM_START_MAGIC_VALUE,
+    pvh_note_type = const XEN_ELFNOTE_PHYS32_ENTRY,
     entry = sym crate::rust_entry,
     entry_secondary = sym crate::rust_entry_secondary,
 
@@ -1,11 +1,17 @@
 //! Uart 16550 serial port.
 
-use axplat::console::ConsoleIf;
+use axplat::console::{ConsoleIf, ConsoleRx};
 use kspin::SpinNoIrq;
 use uart_16550::SerialPort;
 
+/// The ISA IRQ of COM1.
+#[cfg(feature = "irq")]
+const COM1_IRQ: u8 = 4;
+
 static COM1: SpinNoIrq<SerialPort> = unsafe { SpinNoIrq::new(SerialPort::new(0x3f8)) };
 
+static RX: ConsoleRx<256> = ConsoleRx::new();
+
 /// Writes a byte to the console.
 pub fn putchar(c: u8) {
     COM1.lock().send(c)
@@ -20,6 +26,23 @@ pub fn init() {
     COM1.lock().init();
 }
 
+/// Routes the COM1 IRQ to a vector and enables the receive interrupt, after
+/// the IO APIC is initialized.
+#[cfg(feature = "irq")]
+pub fn init_irq() {
+    let Some(vector) = crate::apic::map_isa_irq(COM1_IRQ) else {
+        warn!("failed to map the COM1 IRQ");
+        return;
+    };
+    RX.register_irq(vector, irq_handler);
+}
+
+#[cfg(feature = "irq")]
+fn irq_handler() {
+    let mut com1 = COM1.lock();
+    RX.receive(|| com1.try_receive().ok());
+}
+
 struct ConsoleIfImpl;
 
 #[impl_plat_interface]
@@ -35,15 +58,13 @@ impl ConsoleIf for ConsoleIfImpl {
     ///
     /// Returns the number of bytes read.
     fn read_bytes(bytes: &mut [u8]) -> usize {
-        let mut read_len = 0;
-        while read_len < bytes.len() {
-            if let Some(c) = getchar() {
-                bytes[read_len] = c;
-            } else {
-                break;
-            }
-            read_len += 1;
-        }
-        read_len
+        let mut com1 = COM1.lock();
+        RX.read(bytes, || com1.try_receive().ok())
+    }
+
+    /// Returns the IRQ number of the console input, or `None` if the console
+    /// input is not interrupt-driven.
+    fn irq_num() -> Option<usize> {
+        RX.irq_num()
     }
 }
@@ -0,0 +1,201 @@
+//! High Precision Event Timer (HPET) support.
+//!
+//! The HPET main counter can be used as the clock source if the TSC is not
+//! invariant, and its timer 0 can be used for one-shot timer interrupts if the
+//! LAPIC timer stops in deep C-states.
+
+use axplat::time::ClockSource;
+use lazyinit::LazyInit;
+use memory_addr::{PhysAddr, VirtAddr};
+
+use crate::mem::phys_to_virt;
+
+/// The default HPET base, if it is not described by the ACPI HPET table.
+const HPET_BASE: PhysAddr = pa!(0xFED0_0000);
+
+/// General capabilities and ID register.
+const GCAP_ID: usize = 0x000;
+/// General configuration register.
+const GEN_CONF: usize = 0x010;
+/// Main counter value register.
+const MAIN_COUNTER: usize = 0x0f0;
+
+/// Timer N configuration and capability register.
+#[cfg(feature = "irq")]
+const fn timer_conf(n: usize) -> usize {
+    0x100 + 0x20 * n
+}
+
+/// Timer N comparator value register.
+#[cfg(feature = "irq")]
+const fn timer_comparator(n: usize) -> usize {
+    0x108 + 0x20 * n
+}
+
+/// The main counter is 64-bit.
+const GCAP_COUNT_SIZE_64: u64 = 1 << 13;
+/// Enables the main counter and timer interrupts.
+const GEN_CONF_ENABLE: u64 = 1 << 0;
+/// Legacy replacement routing of timer 0 and 1.
+const GEN_CONF_LEGACY_RT: u64 = 1 << 1;
+/// The maximum counter period in femtoseconds allowed by the specification.
+const MAX_PERIOD_FS: u64 = 100_000_000;
+const FS_PER_SEC: u64 = 1_000_000_000_000_000;
+
+/// Enables the timer interrupt.
+#[cfg(feature = "irq")]
+const TN_INT_ENB: u64 = 1 << 2;
+/// Periodic mode.
+#[cfg(feature = "irq")]
+const TN_TYPE_PERIODIC: u64 = 1 << 3;
+/// Forces a 64-bit timer to operate as a 32-bit timer.
+#[cfg(feature = "irq")]
+const TN_32MODE: u64 = 1 << 8;
+/// The IO APIC pin the timer interrupt is routed to.
+#[cfg(feature = "irq")]
+const TN_INT_ROUTE_SHIFT: u32 = 9;
+#[cfg(feature = "irq")]
+const TN_INT_ROUTE_MASK: u64 = 0x1f << TN_INT_ROUTE_SHIFT;
+/// FSB (MSI) interrupt delivery.
+#[cfg(feature = "irq")]
+const TN_FSB_EN: u64 = 1 << 14;
+
+/// The minimum number of ticks between now and a comparator value, so that
+/// the counter does not pass it before it is written.
+#[cfg(feature = "irq")]
+const MIN_DELTA_TICKS: u64 = 64;
+
+struct Hpet {
+    base: VirtAddr,
+    clock: ClockSource,
+}
+
+impl Hpet {
+    fn read(&self, reg: usize) -> u64 {
+        unsafe { (self.base + reg).as_ptr_of::<u64>().read_volatile() }
+    }
+
+    fn write(&self, reg: usize, value: u64) {
+        unsafe {
+            (self.base + reg)
+                .as_mut_ptr_of::<u64>()
+                .write_volatile(value)
+        }
+    }
+}
+
+static HPET: LazyInit<Hpet> = LazyInit::new();
+
+/// Detects the HPET and starts its main counter.
+///
+/// Returns `false` if no HPET is present.
+pub fn init() -> bool {
+    let hpet = Hpet {
+        base: phys_to_virt(
+            crate::acpi::info()
+                .and_then(|info| info.hpet_paddr)
+                .unwrap_or(HPET_BASE),
+        ),
+        clock: ClockSource::zero(),
+    };
+    let cap = hpet.read(GCAP_ID);
+    let period_fs = cap >> 32;
+    if cap == u64::MAX || period_fs == 0 || period_fs > MAX_PERIOD_FS {
+        return false;
+    }
+    let bits = if cap & GCAP_COUNT_SIZE_64 != 0 {
+        64
+    } else {
+        32
+    };
+    let clock = ClockSource::new(FS_PER_SEC / period_fs, bits);
+
+    let conf = hpet.read(GEN_CONF) & !GEN_CONF_LEGACY_RT;
+    hpet.write(GEN_CONF, conf | GEN_CONF_ENABLE);
+    HPET.init_once(Hpet { clock, ..hpet });
+    true
+}
+
+/// Returns whether the HPET is present and initialized.
+pub fn is_present() -> bool {
+    HPET.is_inited()
+}
+
+/// Returns the clock source of the HPET main counter.
+pub fn clock() -> ClockSource {
+    HPET.clock
+}
+
+/// Reads the HPET main counter.
+pub fn counter() -> u64 {
+    HPET.read(MAIN_COUNTER) & HPET.clock.mask()
+}
+
+/// Measures the frequency of a counter using the HPET as the reference clock.
+///
+/// The counter is read by `read_counter` at the start and the end of an
+/// interval of `ms` milliseconds.
+pub fn calibrate(ms: u64, mut read_counter: impl FnMut() -> u64) -> u64 {
+    let clock = HPET.clock;
+    let ticks = clock.nanos_to_ticks(ms * axplat::time::NANOS_PER_MILLIS);
+    let hpet_start = counter();
+    let start = read_counter();
+    let mut hpet_end = hpet_start;
+    while clock.delta(hpet_end, hpet_start) < ticks {
+        core::hint::spin_loop();
+        hpet_end = counter();
+    }
+    let end = read_counter();
+    let elapsed_ns = clock.ticks_to_nanos(clock.delta(hpet_end, hpet_start));
+    (end.wrapping_sub(start) as u128 * axplat::time::NANOS_PER_SEC as u128 / elapsed_ns as u128)
+        as u64
+}
+
+/// Sets up timer 0 to deliver one-shot timer interrupts to the given vector
+/// on the BSP.
+///
+/// Returns `false` if timer 0 cannot be routed to an IO APIC pin.
+#[cfg(feature = "irq")]
+pub fn init_oneshot(vector: u8) -> bool {
+    let conf = HPET.read(timer_conf(0));
+    let route_cap = (conf >> 32) as u32;
+    if route_cap == 0 {
+        return false;
+    }
+    let pin = route_cap.trailing_zeros() as u8;
+
+    let mut conf = conf & !(TN_TYPE_PERIODIC | TN_FSB_EN | TN_INT_ROUTE_MASK);
+    conf |= ((pin as u64) << TN_INT_ROUTE_SHIFT) | TN_INT_ENB;
+    if HPET.clock.mask() != u64::MAX {
+        conf |= TN_32MODE;
+    }
+    // Do not fire until the first deadline is set.
+    HPET.write(
+        timer_comparator(0),
+        counter().wrapping_sub(1) & HPET.clock.mask(),
+    );
+    HPET.write(timer_conf(0), conf);
+    crate::apic::route_irq(pin as u32, vector);
+    true
+}
+
+/// Sets the timer 0 comparator to trigger an interrupt after the given
+/// nanoseconds.
+#[cfg(feature = "irq")]
+pub fn set_oneshot(delta_ns: u64) {
+    let clock = HPET.clock;
+    let mut delta = clock
+        .nanos_to_ticks(delta_ns)
+        .clamp(MIN_DELTA_TICKS, clock.mask() >> 1);
+    loop {
+        let now = counter();
+        let deadline = now.wrapping_add(delta) & clock.mask();
+        HPET.write(timer_comparator(0), deadline);
+        // The interrupt is lost if the counter has passed the comparator
+        // before it is written, so retry with a larger delta.
+        if clock.delta(counter(), now) < delta {
+            break;
+        }
+        delta *= 2;
+    }
+}
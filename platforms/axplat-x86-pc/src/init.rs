@@ -12,8 +12,12 @@ impl InitIf for InitIfImpl {
     fn init_early(_cpu_id: usize, mbi: usize) {
         axcpu::init::init_trap();
         crate::console::init();
-        crate::time::init_early();
         crate::mem::init(mbi);
+        crate::acpi::init(crate::mem::acpi_rsdp());
+        crate::apic::init_cpu_ids();
+        axplat::console::set_cpu_id_fn(|| crate::apic::current_cpu_id().unwrap_or(0));
+        crate::time::init_early();
+        crate::power::init_idle_states();
     }
 
     /// Initializes the platform at the early stage for secondary cores.
@@ -27,8 +31,16 @@ impl InitIf for InitIfImpl {
     /// initialization (e.g, logging, memory management), and finalized the rest of
     /// platform configuration and initialization.
     fn init_later(_cpu_id: usize, _arg: usize) {
+        #[cfg(feature = "klog")]
+        {
+            use crate::config::plat::{KLOG_PADDR, KLOG_SIZE};
+            // SAFETY: the range is reserved for the kernel log buffer only.
+            unsafe { axplat::console::klog::set_reserved_buffer((KLOG_PADDR, KLOG_SIZE)) };
+        }
         crate::apic::init_primary();
         crate::time::init_primary();
+        #[cfg(feature = "irq")]
+        crate::console::init_irq();
     }
 
     /// Initializes the platform at the later stage for secondary cores.
@@ -8,14 +8,21 @@ extern crate memory_addr;
 #[macro_use]
 extern crate axplat;
 
+pub mod acpi;
 mod apic;
 mod boot;
 mod console;
+mod hpet;
 mod init;
 mod mem;
 mod power;
 mod time;
 
+#[cfg(feature = "irq")]
+pub use self::apic::{Polarity, TriggerMode, map_gsi, map_isa_irq, set_irq_target};
+pub use self::apic::{cpu_apic_id, cpu_count};
+pub use self::mem::{BootModule, Framebuffer, cmdline, framebuffer, modules};
+
 #[cfg(feature = "smp")]
 mod mp;
 
@@ -28,22 +35,18 @@ mod config {
     );
 }
 
-fn current_cpu_id() -> usize {
-    match raw_cpuid::CpuId::new().get_feature_info() {
-        Some(finfo) => finfo.initial_local_apic_id() as usize,
-        None => 0,
-    }
-}
-
 unsafe extern "C" fn rust_entry(magic: usize, mbi: usize) {
-    if magic == self::boot::MULTIBOOT_BOOTLOADER_MAGIC {
-        axplat::call_main(current_cpu_id(), mbi);
+    // the BSP is always logical CPU 0
+    if self::boot::set_boot_protocol(magic) {
+        axplat::call_main(0, mbi);
     }
 }
 
 unsafe extern "C" fn rust_entry_secondary(_magic: usize) {
     #[cfg(feature = "smp")]
-    if _magic == self::boot::MULTIBOOT_BOOTLOADER_MAGIC {
-        axplat::call_secondary_main(current_cpu_id());
+    if _magic == self::boot::MULTIBOOT_BOOTLOADER_MAGIC
+        && let Some(cpu_id) = self::apic::current_cpu_id()
+    {
+        axplat::call_secondary_main(cpu_id);
     }
 }
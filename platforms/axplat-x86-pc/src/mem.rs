@@ -1,35 +1,503 @@
 //! Physical memory information.
+//!
+//! The memory map and other boot information (command line, modules,
+//! framebuffer and ACPI RSDP) are parsed from the multiboot (or multiboot2)
+//! information structure, or the PVH `hvm_start_info` structure passed by the
+//! bootloader.
 
-use axplat::mem::{MemIf, RawRange};
-use heapless::Vec;
+use axplat::mem::{MemIf, RawRange, merge_ranges, normalize_ranges, ranges_contain};
+use heapless::{String, Vec};
 use lazyinit::LazyInit;
-use memory_addr::{PhysAddr, VirtAddr};
+use memory_addr::{PhysAddr, VirtAddr, align_down_4k, align_up_4k};
 use multiboot::information::{MemoryManagement, MemoryType, Multiboot, PAddr};
 
+use crate::boot::BootProtocol;
 use crate::config::devices::MMIO_RANGES;
-use crate::config::plat::PHYS_VIRT_OFFSET;
+use crate::config::plat::{
+    KERNEL_BASE_PADDR, KERNEL_BASE_VADDR, KLOG_PADDR, KLOG_SIZE, PHYS_BUS_OFFSET, PHYS_VIRT_OFFSET,
+};
 
-const MAX_REGIONS: usize = 16;
+const MAX_RAM_REGIONS: usize = 32;
+const MAX_RESERVED_REGIONS: usize = 32;
+const MAX_MODULES: usize = 8;
+/// The kernel command line is truncated to this length.
+const MAX_CMDLINE_LEN: usize = 512;
+/// Module command lines are truncated to this length.
+const MAX_MODULE_CMDLINE_LEN: usize = 64;
 
-static RAM_REGIONS: LazyInit<Vec<RawRange, MAX_REGIONS>> = LazyInit::new();
+/// Lower 1MiB memory is reserved and not allocatable.
+const LOW_MEMORY: RawRange = (0, 0x100000);
+
+/// Reserved ranges from the firmware memory map above this address are
+/// usually address holes (e.g., the HyperTransport range on AMD) rather than
+/// memory, so they are not reported.
+const RESERVED_LIMIT: usize = 0x1_0000_0000;
+
+/// The size of the multiboot information structure.
+const MB1_INFO_SIZE: usize = 120;
+/// The `mmap_*` fields of the multiboot information structure are valid.
+const MB1_INFO_MEM_MAP: u32 = 1 << 6;
+
+// Multiboot2 boot information tag types.
+const MB2_TAG_END: u32 = 0;
+const MB2_TAG_CMDLINE: u32 = 1;
+const MB2_TAG_MODULE: u32 = 3;
+const MB2_TAG_MMAP: u32 = 6;
+const MB2_TAG_FRAMEBUFFER: u32 = 8;
+const MB2_TAG_ACPI_OLD: u32 = 14;
+const MB2_TAG_ACPI_NEW: u32 = 15;
+const MB2_TAG_EFI_MMAP: u32 = 17;
+
+/// The size of a multiboot2 memory map entry defined by the specification.
+const MB2_MMAP_ENTRY_SIZE: usize = 24;
+
+/// The size of a PVH memory map entry (`hvm_memmap_table_entry`).
+const PVH_MEMMAP_ENTRY_SIZE: usize = 24;
+/// The size of a PVH module list entry (`hvm_modlist_entry`).
+const PVH_MODLIST_ENTRY_SIZE: usize = 32;
+/// The size of `hvm_start_info` (version 1).
+const PVH_START_INFO_SIZE: usize = 56;
+
+/// The size of a version 1 EFI memory descriptor.
+const EFI_DESC_SIZE: usize = 40;
+const EFI_PAGE_SIZE: u64 = 0x1000;
+
+/// A module loaded by the bootloader.
+#[derive(Debug, Clone)]
+pub struct BootModule {
+    range: RawRange,
+    cmdline: String<MAX_MODULE_CMDLINE_LEN>,
+}
+
+impl BootModule {
+    /// Returns the physical memory range of the module.
+    pub const fn range(&self) -> RawRange {
+        self.range
+    }
+
+    /// Returns the command line (usually the name) of the module.
+    pub fn cmdline(&self) -> &str {
+        &self.cmdline
+    }
+}
+
+/// The framebuffer set up by the bootloader.
+#[derive(Debug, Clone, Copy)]
+pub struct Framebuffer {
+    /// The physical address of the framebuffer.
+    pub paddr: PhysAddr,
+    /// The number of bytes per line.
+    pub pitch: usize,
+    /// The width in pixels (or characters in EGA text mode).
+    pub width: usize,
+    /// The height in pixels (or characters in EGA text mode).
+    pub height: usize,
+    /// The number of bits per pixel.
+    pub bpp: u8,
+    /// The framebuffer type: 0 for indexed color, 1 for direct RGB color and
+    /// 2 for EGA text.
+    pub kind: u8,
+}
+
+/// The kind of a memory map entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegionKind {
+    /// Free RAM.
+    Ram,
+    /// Memory used by the firmware (e.g., ACPI tables and NVS).
+    Reserved,
+    /// Defective memory or types not recognized.
+    Unusable,
+}
+
+impl RegionKind {
+    /// Converts an E820 memory type, which is also used by the multiboot2 and
+    /// PVH memory maps.
+    const fn from_e820(ty: u32) -> Self {
+        match ty {
+            1 => Self::Ram,
+            // reserved, ACPI reclaimable and ACPI NVS
+            2..=4 => Self::Reserved,
+            _ => Self::Unusable,
+        }
+    }
+
+    /// Converts an EFI memory type.
+    const fn from_efi(ty: u32) -> Self {
+        match ty {
+            // loader code/data, boot services code/data and conventional
+            // memory, which are free to use after the boot services exit
+            1..=4 | 7 => Self::Ram,
+            // reserved, runtime services code/data, ACPI reclaimable and
+            // ACPI NVS
+            0 | 5 | 6 | 9 | 10 => Self::Reserved,
+            _ => Self::Unusable,
+        }
+    }
+}
+
+/// Sorts the ranges and merges the overlapping or adjacent ones.
+fn normalize<const N: usize>(ranges: &mut Vec<RawRange, N>) {
+    let len = merge_ranges(ranges);
+    ranges.truncate(len);
+}
+
+/// Appends a range, merging the existing ranges to make room if it is full.
+///
+/// Returns `false` if there is still no room.
+fn push_range<const N: usize>(ranges: &mut Vec<RawRange, N>, range: RawRange) -> bool {
+    if ranges.is_full() {
+        normalize(ranges);
+    }
+    ranges.push(range).is_ok()
+}
+
+#[derive(Default)]
+struct BootInfo {
+    ram: Vec<RawRange, MAX_RAM_REGIONS>,
+    reserved: Vec<RawRange, MAX_RESERVED_REGIONS>,
+    cmdline: String<MAX_CMDLINE_LEN>,
+    modules: Vec<BootModule, MAX_MODULES>,
+    framebuffer: Option<Framebuffer>,
+    acpi_rsdp: Option<PhysAddr>,
+}
+
+impl BootInfo {
+    /// Adds an entry of the firmware memory map.
+    fn add_region(&mut self, base: usize, size: usize, kind: RegionKind) {
+        if size == 0 {
+            return;
+        }
+        match kind {
+            RegionKind::Ram => {
+                if !push_range(&mut self.ram, (base, size)) {
+                    warn!("too many RAM regions, ignoring {:#x?}", base..base + size);
+                }
+            }
+            RegionKind::Reserved if base < RESERVED_LIMIT => {
+                self.add_reserved(base, size.min(RESERVED_LIMIT - base));
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a reserved range, extended to page boundaries.
+    ///
+    /// Returns `false` if there are too many reserved ranges.
+    fn add_reserved(&mut self, base: usize, size: usize) -> bool {
+        let start = align_down_4k(base);
+        let end = align_up_4k(base + size);
+        if push_range(&mut self.reserved, (start, end - start)) {
+            true
+        } else {
+            warn!("too many reserved regions, ignoring {:#x?}", start..end);
+            false
+        }
+    }
+
+    fn add_module(&mut self, start: usize, end: usize, cmdline: &str) {
+        let range = (start, end.saturating_sub(start));
+        if !self.add_reserved(range.0, range.1) {
+            warn!("ignoring module {cmdline:?}");
+            return;
+        }
+        let module = BootModule {
+            range,
+            cmdline: truncated(cmdline),
+        };
+        if self.modules.push(module).is_err() {
+            warn!("too many modules, ignoring module {cmdline:?}");
+        }
+    }
+
+    /// Finishes the collection: merges the ranges, reserves the range of the
+    /// kernel log buffer (with the `klog` feature), and removes device memory
+    /// (mapped separately) and the kernel image from the reserved ranges.
+    fn finish(&mut self) {
+        normalize(&mut self.ram);
+        if cfg!(feature = "klog") && KLOG_SIZE > 0 {
+            if ranges_contain(&self.ram, (KLOG_PADDR, KLOG_SIZE)) {
+                self.add_reserved(KLOG_PADDR, KLOG_SIZE);
+            } else {
+                warn!(
+                    "klog range {:#x?} is not in RAM, ignoring",
+                    KLOG_PADDR..KLOG_PADDR + KLOG_SIZE
+                );
+            }
+        }
+
+        let mut exclude = [kernel_image_range(); MMIO_RANGES.len() + 1];
+        exclude[1..].copy_from_slice(MMIO_RANGES);
+        let len = merge_ranges(&mut exclude);
+        let mut reserved = Vec::new();
+        normalize_ranges(&mut self.reserved, &exclude[..len], |r| {
+            if reserved.push(r).is_err() {
+                warn!("too many reserved regions, ignoring {r:#x?}");
+            }
+        });
+        self.reserved = reserved;
+    }
+}
+
+static BOOT_INFO: LazyInit<BootInfo> = LazyInit::new();
+
+/// Returns the physical memory range where the kernel image is loaded.
+fn kernel_image_range() -> RawRange {
+    unsafe extern "C" {
+        fn _ekernel();
+    }
+    (KERNEL_BASE_PADDR, _ekernel as usize - KERNEL_BASE_VADDR)
+}
 
 pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
     va!(paddr.as_usize() + PHYS_VIRT_OFFSET)
 }
 
-pub fn init(multiboot_info_ptr: usize) {
+pub const fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+    pa!(vaddr.as_usize() - PHYS_VIRT_OFFSET)
+}
+
+fn phys_bytes(paddr: usize, size: usize) -> &'static [u8] {
+    unsafe { core::slice::from_raw_parts(phys_to_virt(pa!(paddr)).as_ptr(), size) }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    bytes
+        .get(offset..offset + 4)
+        .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    bytes
+        .get(offset..offset + 8)
+        .map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// Returns the NUL-terminated string at the beginning of `bytes`, or its
+/// valid UTF-8 prefix.
+fn cstr(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let bytes = &bytes[..len];
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => core::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap(),
+    }
+}
+
+/// Returns the NUL-terminated string at the given physical address, truncated
+/// to `max_len` bytes.
+fn phys_cstr(paddr: usize, max_len: usize) -> &'static str {
+    if paddr == 0 {
+        ""
+    } else {
+        cstr(phys_bytes(paddr, max_len))
+    }
+}
+
+/// Copies a string, truncating it at a character boundary if it is too long.
+fn truncated<const N: usize>(s: &str) -> String<N> {
+    let mut out = String::new();
+    for c in s.chars() {
+        if out.push(c).is_err() {
+            break;
+        }
+    }
+    out
+}
+
+/// Parses the multiboot information structure.
+fn parse_multiboot(mbi: usize, info: &mut BootInfo) {
+    let raw = phys_bytes(mbi, MB1_INFO_SIZE);
+    info.add_reserved(mbi, MB1_INFO_SIZE);
+    if read_u32(raw, 0) & MB1_INFO_MEM_MAP != 0 {
+        info.add_reserved(read_u32(raw, 48) as usize, read_u32(raw, 44) as usize);
+    }
+
     let mut mm = MemIfImpl;
-    let info = unsafe { Multiboot::from_ptr(multiboot_info_ptr as _, &mut mm).unwrap() };
-
-    let mut regions = Vec::new();
-    for r in info.memory_regions().unwrap() {
-        if r.memory_type() == MemoryType::Available {
-            regions
-                .push((r.base_address() as usize, r.length() as usize))
-                .unwrap();
+    let mb = unsafe { Multiboot::from_ptr(mbi as _, &mut mm).unwrap() };
+    for r in mb.memory_regions().unwrap() {
+        let kind = match r.memory_type() {
+            MemoryType::Available => RegionKind::Ram,
+            MemoryType::Reserved | MemoryType::ACPI | MemoryType::NVS => RegionKind::Reserved,
+            MemoryType::Defect => RegionKind::Unusable,
+        };
+        info.add_region(r.base_address() as usize, r.length() as usize, kind);
+    }
+    if let Some(cmdline) = mb.command_line() {
+        info.cmdline = truncated(cmdline);
+    }
+    if let Some(modules) = mb.modules() {
+        for m in modules {
+            info.add_module(m.start as usize, m.end as usize, m.string.unwrap_or(""));
+        }
+    }
+}
+
+/// Parses the multiboot2 boot information tags.
+fn parse_multiboot2(mbi: usize, info: &mut BootInfo) {
+    let total_size = read_u32(phys_bytes(mbi, 8), 0) as usize;
+    let data = phys_bytes(mbi, total_size);
+    info.add_reserved(mbi, total_size);
+
+    let mut has_mmap = false;
+    let mut efi_mmap = None;
+    // Tags start after the fixed part and are 8-byte aligned.
+    let mut offset = 8;
+    while offset + 8 <= data.len() {
+        let tag_type = read_u32(data, offset);
+        let size = read_u32(data, offset + 4) as usize;
+        if tag_type == MB2_TAG_END || size < 8 {
+            break;
+        }
+        let tag = &data[offset..(offset + size).min(data.len())];
+        match tag_type {
+            MB2_TAG_CMDLINE => info.cmdline = truncated(cstr(&tag[8..])),
+            MB2_TAG_MODULE => info.add_module(
+                read_u32(tag, 8) as usize,
+                read_u32(tag, 12) as usize,
+                cstr(tag.get(16..).unwrap_or_default()),
+            ),
+            MB2_TAG_MMAP => {
+                has_mmap = true;
+                let entry_size = read_u32(tag, 8) as usize;
+                if entry_size >= MB2_MMAP_ENTRY_SIZE {
+                    for entry in tag.get(16..).unwrap_or_default().chunks_exact(entry_size) {
+                        let kind = RegionKind::from_e820(read_u32(entry, 16));
+                        info.add_region(
+                            read_u64(entry, 0) as usize,
+                            read_u64(entry, 8) as usize,
+                            kind,
+                        );
+                    }
+                }
+            }
+            MB2_TAG_EFI_MMAP => efi_mmap = Some(tag),
+            MB2_TAG_FRAMEBUFFER => {
+                info.framebuffer = Some(Framebuffer {
+                    paddr: pa!(read_u64(tag, 8) as usize),
+                    pitch: read_u32(tag, 16) as usize,
+                    width: read_u32(tag, 20) as usize,
+                    height: read_u32(tag, 24) as usize,
+                    bpp: tag.get(28).copied().unwrap_or(0),
+                    kind: tag.get(29).copied().unwrap_or(0),
+                })
+            }
+            // The tag holds a copy of the RSDP. Prefer the ACPI 2.0+ one.
+            MB2_TAG_ACPI_OLD | MB2_TAG_ACPI_NEW => {
+                if tag_type == MB2_TAG_ACPI_NEW || info.acpi_rsdp.is_none() {
+                    info.acpi_rsdp = Some(pa!(mbi + offset + 8));
+                }
+            }
+            _ => {}
+        }
+        offset += size.next_multiple_of(8);
+    }
+
+    // The EFI memory map is only used if there is no multiboot2 memory map.
+    if let Some(tag) = efi_mmap.filter(|_| !has_mmap) {
+        let desc_size = read_u32(tag, 8) as usize;
+        if desc_size >= EFI_DESC_SIZE {
+            for desc in tag.get(16..).unwrap_or_default().chunks_exact(desc_size) {
+                let kind = RegionKind::from_efi(read_u32(desc, 0));
+                let base = read_u64(desc, 8) as usize;
+                let pages = read_u64(desc, 24);
+                info.add_region(base, (pages * EFI_PAGE_SIZE) as usize, kind);
+            }
+        }
+    }
+}
+
+/// Parses the PVH start information structure.
+fn parse_pvh(start_info: usize, info: &mut BootInfo) {
+    let data = phys_bytes(start_info, PVH_START_INFO_SIZE);
+    if read_u32(data, 0) as usize != crate::boot::XEN_HVM_START_MAGIC_VALUE {
+        warn!("invalid PVH start info at {start_info:#x}");
+        return;
+    }
+    let version = read_u32(data, 4);
+    info.add_reserved(start_info, PVH_START_INFO_SIZE);
+
+    info.cmdline = truncated(phys_cstr(read_u64(data, 24) as usize, MAX_CMDLINE_LEN));
+    let nr_modules = read_u32(data, 12) as usize;
+    let modlist_paddr = read_u64(data, 16) as usize;
+    let modlist = phys_bytes(modlist_paddr, nr_modules * PVH_MODLIST_ENTRY_SIZE);
+    if nr_modules > 0 {
+        info.add_reserved(modlist_paddr, modlist.len());
+    }
+    for entry in modlist.chunks_exact(PVH_MODLIST_ENTRY_SIZE) {
+        let start = read_u64(entry, 0) as usize;
+        let size = read_u64(entry, 8) as usize;
+        let cmdline = phys_cstr(read_u64(entry, 16) as usize, MAX_MODULE_CMDLINE_LEN);
+        info.add_module(start, start + size, cmdline);
+    }
+    let rsdp_paddr = read_u64(data, 32) as usize;
+    if rsdp_paddr != 0 {
+        info.acpi_rsdp = Some(pa!(rsdp_paddr));
+    }
+
+    // The memory map is only present since version 1.
+    if version >= 1 {
+        let entries = read_u32(data, 48) as usize;
+        let memmap_paddr = read_u64(data, 40) as usize;
+        let memmap = phys_bytes(memmap_paddr, entries * PVH_MEMMAP_ENTRY_SIZE);
+        if entries > 0 {
+            info.add_reserved(memmap_paddr, memmap.len());
         }
+        for entry in memmap.chunks_exact(PVH_MEMMAP_ENTRY_SIZE) {
+            let kind = RegionKind::from_e820(read_u32(entry, 16));
+            info.add_region(
+                read_u64(entry, 0) as usize,
+                read_u64(entry, 8) as usize,
+                kind,
+            );
+        }
+    }
+    if info.ram.is_empty() {
+        warn!("no memory map in PVH start info");
+    }
+}
+
+/// Parses the boot information passed by the bootloader.
+pub fn init(mbi: usize) {
+    let mut info = BootInfo::default();
+    info.add_reserved(LOW_MEMORY.0, LOW_MEMORY.1);
+    match crate::boot::boot_protocol() {
+        BootProtocol::Multiboot => parse_multiboot(mbi, &mut info),
+        BootProtocol::Multiboot2 => parse_multiboot2(mbi, &mut info),
+        BootProtocol::Pvh => parse_pvh(mbi, &mut info),
     }
-    RAM_REGIONS.init_once(regions);
+    info.finish();
+    debug!(
+        "boot info: cmdline {:?}, {} modules, framebuffer {:x?}",
+        info.cmdline,
+        info.modules.len(),
+        info.framebuffer
+    );
+    BOOT_INFO.init_once(info);
+}
+
+/// Returns the kernel command line passed by the bootloader.
+pub fn cmdline() -> &'static str {
+    &BOOT_INFO.cmdline
+}
+
+/// Returns the modules loaded by the bootloader.
+///
+/// Their memory is included in the reserved physical memory ranges.
+pub fn modules() -> &'static [BootModule] {
+    &BOOT_INFO.modules
+}
+
+/// Returns the framebuffer set up by the bootloader, which is only reported
+/// by multiboot2 bootloaders.
+pub fn framebuffer() -> Option<Framebuffer> {
+    BOOT_INFO.framebuffer
+}
+
+/// Returns the physical address of the ACPI RSDP passed by a multiboot2
+/// bootloader or the PVH start information, if any.
+pub(crate) fn acpi_rsdp() -> Option<PhysAddr> {
+    BOOT_INFO.acpi_rsdp
 }
 
 struct MemIfImpl;
@@ -51,18 +519,34 @@ impl MemoryManagement for MemIfImpl {
 impl MemIf for MemIfImpl {
     /// Returns all physical memory (RAM) ranges on the platform.
     fn phys_ram_ranges() -> &'static [RawRange] {
-        RAM_REGIONS.as_slice()
+        &BOOT_INFO.ram
     }
 
     /// Returns all reserved physical memory ranges on the platform.
     ///
-    /// Lower 1MiB memory is reserved and not allocatable.
+    /// Lower 1MiB memory, firmware reserved memory (e.g., ACPI tables and NVS),
+    /// the boot information and boot modules are reserved and not allocatable.
     fn reserved_phys_ram_ranges() -> &'static [RawRange] {
-        &[(0, 0x100000)]
+        &BOOT_INFO.reserved
     }
 
     /// Returns all device memory (MMIO) ranges on the platform.
     fn mmio_ranges() -> &'static [RawRange] {
         &MMIO_RANGES
     }
+
+    /// Translates a physical address to a virtual address.
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        phys_to_virt(paddr)
+    }
+
+    /// Translates a virtual address to a physical address.
+    fn virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+        virt_to_phys(vaddr)
+    }
+
+    /// Returns the offset between bus addresses and physical addresses.
+    fn phys_bus_offset() -> usize {
+        PHYS_BUS_OFFSET
+    }
 }
@@ -32,11 +32,15 @@ unsafe fn setup_startup_page(stack_top: PhysAddr) {
     start_page[U64_PER_PAGE - 1] = ap_entry32 as usize as _; // entry
 }
 
-/// Starts the given secondary CPU with its boot stack.
-pub fn start_secondary_cpu(apic_id: usize, stack_top: PhysAddr) {
+/// Starts the given secondary CPU (by the logical CPU ID) with its boot stack.
+pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
+    let Some(apic_id) = super::apic::cpu_apic_id(cpu_id) else {
+        warn!("CPU {cpu_id} does not exist");
+        return;
+    };
     unsafe { setup_startup_page(stack_top) };
 
-    let apic_id = super::apic::raw_apic_id(apic_id as u8);
+    let apic_id = super::apic::raw_apic_id(apic_id);
     let lapic = super::apic::local_apic();
 
     // INIT-SIPI-SIPI Sequence
@@ -1,10 +1,157 @@
 //! Power management.
 
-use axplat::power::PowerIf;
-use x86_64::instructions::port::PortWriteOnly;
+use axplat::power::{CpuIdleKind, CpuIdleState, PowerIf};
+use heapless::Vec;
+use lazyinit::LazyInit;
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+/// The maximum number of idle states.
+const MAX_IDLE_STATES: usize = 8;
+
+/// Names, kinds, and latencies (in microseconds) of MWAIT C-states C2 ~ C7.
+///
+/// The latencies are typical values, as the precise ones can only be obtained
+/// from ACPI `_CST` objects.
+const MWAIT_CSTATES: [(&str, CpuIdleKind, u32); 6] = [
+    ("C2-MWAIT", CpuIdleKind::Retention, 10),
+    ("C3-MWAIT", CpuIdleKind::PowerDown, 70),
+    ("C4-MWAIT", CpuIdleKind::PowerDown, 85),
+    ("C5-MWAIT", CpuIdleKind::PowerDown, 124),
+    ("C6-MWAIT", CpuIdleKind::PowerDown, 200),
+    ("C7-MWAIT", CpuIdleKind::PowerDown, 480),
+];
+
+/// Supported idle states, the first one is always `hlt`.
+static IDLE_STATES: LazyInit<Vec<CpuIdleState, MAX_IDLE_STATES>> = LazyInit::new();
+
+/// MWAIT hints of idle states in [`IDLE_STATES`] except the first one.
+static MWAIT_HINTS: LazyInit<Vec<u32, MAX_IDLE_STATES>> = LazyInit::new();
+
+/// Detects supported idle states by `CPUID`.
+pub(crate) fn init_idle_states() {
+    let mut states = Vec::new();
+    let mut hints = Vec::new();
+    states
+        .push(CpuIdleState::new("hlt", CpuIdleKind::Standby, 1, 1))
+        .unwrap();
+
+    let cpuid = raw_cpuid::CpuId::new();
+    let has_mwait = cpuid
+        .get_feature_info()
+        .is_some_and(|f| f.has_monitor_mwait());
+    if let Some(info) = cpuid.get_monitor_mwait_info().filter(|_| has_mwait) {
+        let substates = [
+            info.supported_c2_states(),
+            info.supported_c3_states(),
+            info.supported_c4_states(),
+            info.supported_c5_states(),
+            info.supported_c6_states(),
+            info.supported_c7_states(),
+        ];
+        for (i, &(name, kind, latency)) in MWAIT_CSTATES.iter().enumerate() {
+            if substates[i] > 0 {
+                // EAX[7:4] = C-state - 1, EAX[3:0] = sub C-state
+                let hint = ((i as u32) + 1) << 4;
+                states
+                    .push(CpuIdleState::new(name, kind, latency, latency))
+                    .unwrap();
+                hints.push(hint).unwrap();
+            }
+        }
+    }
+    IDLE_STATES.init_once(states);
+    MWAIT_HINTS.init_once(hints);
+}
+
+/// Enters an MWAIT C-state with the given hint.
+fn mwait(hint: u32) {
+    static MONITOR_TARGET: u64 = 0;
+    unsafe {
+        core::arch::asm!(
+            "monitor",
+            in("rax") &raw const MONITOR_TARGET,
+            in("ecx") 0,
+            in("edx") 0,
+        );
+        core::arch::asm!("mwait", in("eax") hint, in("ecx") 0);
+    }
+}
 
 struct PowerImpl;
 
+/// Resets the system through the ACPI reset register if supported.
+fn acpi_reset() {
+    use crate::acpi::GenericAddress;
+
+    let Some((reg, value)) = crate::acpi::info().and_then(|info| info.reset_reg) else {
+        return;
+    };
+    match reg.space_id {
+        GenericAddress::SPACE_IO => unsafe { PortWriteOnly::new(reg.address as u16).write(value) },
+        GenericAddress::SPACE_MEMORY => unsafe {
+            crate::mem::phys_to_virt(pa!(reg.address as usize))
+                .as_mut_ptr()
+                .write_volatile(value)
+        },
+        _ => return,
+    }
+    axplat::time::busy_wait(axplat::time::Duration::from_millis(10));
+}
+
+/// Enters the ACPI S5 (soft off) state if the PM1 control blocks are known.
+fn acpi_power_off() {
+    /// Enables the SCI interrupt, i.e., the system is in ACPI mode.
+    const SCI_EN: u16 = 1 << 0;
+    const SLP_TYP_SHIFT: u16 = 10;
+    const SLP_EN: u16 = 1 << 13;
+
+    let Some(info) = crate::acpi::info() else {
+        return;
+    };
+    let Some(pm1a_cnt) = info.pm1a_cnt_port else {
+        return;
+    };
+    // SLP_TYP 0 is S5 on QEMU if `\_S5` is not found in the DSDT
+    let (slp_typa, slp_typb) = info.s5_sleep_types.unwrap_or((0, 0));
+    unsafe {
+        let mut pm1a = Port::<u16>::new(pm1a_cnt);
+        if pm1a.read() & SCI_EN == 0 && info.smi_cmd_port != 0 && info.acpi_enable != 0 {
+            PortWriteOnly::new(info.smi_cmd_port).write(info.acpi_enable);
+            for _ in 0..300 {
+                if pm1a.read() & SCI_EN != 0 {
+                    break;
+                }
+                axplat::time::busy_wait(axplat::time::Duration::from_millis(10));
+            }
+        }
+        pm1a.write(((slp_typa as u16) << SLP_TYP_SHIFT) | SLP_EN);
+        if let Some(pm1b_cnt) = info.pm1b_cnt_port {
+            PortWriteOnly::new(pm1b_cnt).write(((slp_typb as u16) << SLP_TYP_SHIFT) | SLP_EN);
+        }
+    }
+}
+
+/// Resets the system through the ACPI reset register, the keyboard controller,
+/// or the reset control register (port `0xcf9`), until one of them works.
+///
+/// See <https://wiki.osdev.org/Reboot> for more information.
+fn reset() -> ! {
+    acpi_reset();
+    unsafe {
+        PortWriteOnly::new(0x64).write(0xfeu8);
+        axplat::time::busy_wait(axplat::time::Duration::from_millis(10));
+        // full reset: system reset (bit 1) + reset CPU (bit 2) + full reset (bit 3)
+        let mut reset_ctrl = PortWriteOnly::<u8>::new(0xcf9);
+        reset_ctrl.write(0x02);
+        reset_ctrl.write(0x0e);
+    }
+    axcpu::asm::halt();
+    warn!("It should reset!");
+    loop {
+        axcpu::asm::halt();
+    }
+}
+
 #[impl_plat_interface]
 impl PowerIf for PowerImpl {
     /// Bootstraps the given CPU core with the given initial stack (in physical
@@ -27,7 +174,33 @@ impl PowerIf for PowerImpl {
         }
     }
 
-    /// Shutdown the whole system (in QEMU).
+    /// Returns all idle states supported by the calling CPU.
+    fn cpu_idle_states() -> &'static [CpuIdleState] {
+        IDLE_STATES.as_slice()
+    }
+
+    /// Puts the calling CPU into the idle state at `index`.
+    ///
+    /// The first state is entered by `hlt`, others are entered by `mwait`.
+    fn cpu_idle(index: usize) {
+        match index.checked_sub(1) {
+            Some(i) if !MWAIT_HINTS.is_empty() => mwait(MWAIT_HINTS[i.min(MWAIT_HINTS.len() - 1)]),
+            _ => axcpu::asm::wait_for_irqs(),
+        }
+    }
+
+    /// Parks the calling CPU core with interrupts disabled.
+    ///
+    /// The core stays halted until an INIT IPI is received, which is sent by
+    /// [`cpu_boot`](Self::cpu_boot) to restart it.
+    fn cpu_off() -> ! {
+        axcpu::asm::disable_irqs();
+        loop {
+            axcpu::asm::halt();
+        }
+    }
+
+    /// Shutdown the whole system by entering the ACPI S5 state.
     ///
     /// See <https://wiki.osdev.org/Shutdown> for more information.
     fn system_off() -> ! {
@@ -39,8 +212,10 @@ impl PowerIf for PowerImpl {
             axplat::console_println!("System will reboot, press any key to continue ...");
             while super::console::getchar().is_none() {}
             axplat::console_println!("Rebooting ...");
-            unsafe { PortWriteOnly::new(0x64).write(0xfeu8) };
+            reset();
         } else {
+            acpi_power_off();
+            // QEMU's PM1a control block if the ACPI tables are not found
             unsafe { PortWriteOnly::new(0x604).write(0x2000u16) };
         }
 
@@ -50,4 +225,10 @@ impl PowerIf for PowerImpl {
             axcpu::asm::halt();
         }
     }
+
+    /// Resets (reboots) the whole system.
+    fn system_reset() -> ! {
+        info!("Resetting...");
+        reset()
+    }
 }
@@ -1,38 +1,180 @@
 //! Time management.
 //!
-//! Currently, the TSC is used as the clock source.
+//! The TSC is used as the clock source, and the LAPIC timer is used for
+//! one-shot timer interrupts, in TSC-deadline mode if supported. Their
+//! frequencies are calibrated against the HPET (or the PIT if there is no HPET)
+//! at boot time.
+//!
+//! If the TSC is not invariant, the HPET main counter is used as the clock
+//! source instead. If the LAPIC timer stops in deep C-states (no ARAT), the
+//! HPET timer 0 is used for one-shot timer interrupts instead. It is armed for
+//! the earliest deadline of all CPUs and interrupts the BSP, which forwards
+//! the expired deadlines of other CPUs to them by IPIs.
+
+#[cfg(feature = "irq")]
+use core::sync::atomic::{AtomicU64, Ordering};
 
-use axplat::time::TimeIf;
+use axplat::time::{ClockSource, TimeIf};
+#[cfg(feature = "irq")]
+use kspin::SpinNoIrq;
 use raw_cpuid::CpuId;
+use x86_64::instructions::port::Port;
+
+/// The input clock frequency of the PIT.
+const PIT_FREQ_HZ: u64 = 1_193_182;
+
+/// The duration of a calibration measurement in milliseconds.
+const CALIBRATE_MS: u64 = 50;
+
+#[cfg(feature = "irq")]
+static mut LAPIC_CLOCK: ClockSource = ClockSource::zero();
+
+/// Whether the HPET is used for one-shot timer interrupts.
+#[cfg(feature = "irq")]
+static mut HPET_ONESHOT: bool = false;
 
+/// Marks that a CPU has no pending HPET one-shot deadline.
 #[cfg(feature = "irq")]
-use int_ratio::Ratio;
+const NO_DEADLINE: u64 = u64::MAX;
 
+/// The pending one-shot timer deadlines in nanoseconds of all CPUs, indexed
+/// by the logical CPU ID, if the HPET is used for one-shot timers.
 #[cfg(feature = "irq")]
-const LAPIC_TICKS_PER_SEC: u64 = 1_000_000_000; // TODO: need to calibrate
+static HPET_DEADLINES: [AtomicU64; crate::acpi::MAX_LOCAL_APICS] =
+    [const { AtomicU64::new(NO_DEADLINE) }; crate::acpi::MAX_LOCAL_APICS];
 
+/// Serializes the updates of the HPET timer 0 comparator.
 #[cfg(feature = "irq")]
-static mut NANOS_TO_LAPIC_TICKS_RATIO: Ratio = Ratio::zero();
+static HPET_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
+/// Whether the LAPIC timer is used in TSC-deadline mode.
+#[cfg(feature = "irq")]
+static mut TSC_DEADLINE: bool = false;
+
+/// The pending one-shot timer deadline in nanoseconds, used to re-arm the
+/// LAPIC count-down timer if the deadline is too far away for it.
+#[cfg(feature = "irq")]
+#[percpu::def_percpu]
+static ONESHOT_DEADLINE_NS: u64 = 0;
+
+/// Whether the TSC is the clock source.
+static mut CLOCK_IS_TSC: bool = true;
 
 static mut INIT_TICK: u64 = 0;
-static mut CPU_FREQ_MHZ: u64 = crate::config::devices::TIMER_FREQUENCY as u64 / 1_000_000;
+static mut CLOCK: ClockSource =
+    ClockSource::new(crate::config::devices::TIMER_FREQUENCY as u64, 64);
+/// Reads the counter of the clock source.
+static mut READ_COUNTER: fn() -> u64 = rdtsc;
 
 /// RTC wall time offset in nanoseconds at monotonic time base.
 static mut RTC_EPOCHOFFSET_NANOS: u64 = 0;
 
-pub fn init_early() {
-    if let Some(freq) = CpuId::new()
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Measures the frequency of a counter using the HPET, or the PIT channel 2 if
+/// there is no HPET, as the reference clock.
+fn calibrate(read_counter: impl FnMut() -> u64) -> Option<u64> {
+    if crate::hpet::is_present() {
+        Some(crate::hpet::calibrate(CALIBRATE_MS, read_counter))
+    } else {
+        pit_calibrate(read_counter)
+    }
+}
+
+/// Measures the frequency of a counter using the PIT channel 2 as the
+/// reference clock.
+///
+/// The counter is read by `read_counter` at the start and the end of a
+/// [`CALIBRATE_MS`] interval. It returns `None` if the PIT does not seem to be
+/// present.
+fn pit_calibrate(mut read_counter: impl FnMut() -> u64) -> Option<u64> {
+    /// The maximum number of polls before giving up waiting for the PIT.
+    const MAX_POLLS: usize = 100_000_000;
+    /// The minimum number of polls for a plausible measurement.
+    const MIN_POLLS: usize = 1_000;
+
+    let mut gate = Port::<u8>::new(0x61);
+    let mut command = Port::<u8>::new(0x43);
+    let mut channel2 = Port::<u8>::new(0x42);
+    let latch = PIT_FREQ_HZ * CALIBRATE_MS / 1_000;
+
+    unsafe {
+        // Enable the channel 2 gate, and disable the speaker output.
+        let old_gate = gate.read();
+        gate.write((old_gate & !0x02) | 0x01);
+        // Channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal count).
+        command.write(0xb0);
+        channel2.write(latch as u8);
+        channel2.write((latch >> 8) as u8);
+
+        // The output goes high when the count reaches 0.
+        let start = read_counter();
+        let mut polls = 0;
+        while gate.read() & 0x20 == 0 && polls < MAX_POLLS {
+            polls += 1;
+        }
+        let end = read_counter();
+        gate.write(old_gate);
+
+        if !(MIN_POLLS..MAX_POLLS).contains(&polls) {
+            return None;
+        }
+        Some(end.wrapping_sub(start) * 1_000 / CALIBRATE_MS)
+    }
+}
+
+/// Returns the TSC frequency in Hz.
+///
+/// The frequency enumerated by CPUID leaf 0x15 is exact and preferred. If it
+/// is not available (e.g., under QEMU TCG), the TSC is calibrated against the
+/// HPET or the PIT, and then the nominal frequency of CPUID leaf 0x16 and the configured
+/// `timer-frequency` are used as fallbacks.
+fn tsc_frequency() -> u64 {
+    let cpuid = CpuId::new();
+    if let Some(freq) = cpuid.get_tsc_info().and_then(|info| info.tsc_frequency()) {
+        return freq;
+    }
+    if let Some(freq) = calibrate(rdtsc) {
+        return freq;
+    }
+    if let Some(freq) = cpuid
         .get_processor_frequency_info()
         .map(|info| info.processor_base_frequency())
         && freq > 0
     {
-        unsafe { CPU_FREQ_MHZ = freq as u64 }
+        return freq as u64 * 1_000_000;
     }
+    crate::config::devices::TIMER_FREQUENCY as u64
+}
 
-    axplat::console_println!("TSC frequency: {} MHz", unsafe { CPU_FREQ_MHZ });
+pub fn init_early() {
+    let has_hpet = crate::hpet::init();
+    let invariant_tsc = CpuId::new()
+        .get_advanced_power_mgmt_info()
+        .is_some_and(|info| info.has_invariant_tsc());
+
+    // A 32-bit HPET counter wraps in minutes, so it is not used as the clock
+    // source.
+    if !invariant_tsc && has_hpet && crate::hpet::clock().mask() == u64::MAX {
+        unsafe {
+            CLOCK = crate::hpet::clock();
+            READ_COUNTER = crate::hpet::counter;
+            CLOCK_IS_TSC = false;
+        }
+        axplat::console_println!("HPET frequency: {} MHz", unsafe {
+            CLOCK.freq_hz() / 1_000_000
+        });
+    } else {
+        unsafe { CLOCK = ClockSource::new(tsc_frequency(), 64) }
+        axplat::console_println!("TSC frequency: {} MHz", unsafe {
+            CLOCK.freq_hz() / 1_000_000
+        });
+    }
 
     unsafe {
-        INIT_TICK = core::arch::x86_64::_rdtsc();
+        INIT_TICK = READ_COUNTER();
     }
 
     #[cfg(feature = "rtc")]
@@ -48,20 +190,152 @@ pub fn init_early() {
     }
 }
 
+/// Sets the LAPIC timer mode of the current CPU and enables the timer.
+#[cfg(feature = "irq")]
+unsafe fn init_lapic_timer() {
+    use x2apic::lapic::{TimerDivide, TimerMode};
+    let lapic = super::apic::local_apic();
+    unsafe {
+        if TSC_DEADLINE {
+            lapic.set_timer_mode(TimerMode::TscDeadline);
+        } else {
+            lapic.set_timer_mode(TimerMode::OneShot);
+            lapic.set_timer_divide(TimerDivide::Div256); // indeed it is Div1, the name is confusing.
+        }
+        lapic.enable_timer();
+    }
+}
+
+/// Calibrates the LAPIC count-down timer.
+#[cfg(feature = "irq")]
+unsafe fn calibrate_lapic_timer() {
+    let lapic = super::apic::local_apic();
+    unsafe {
+        // The timer counts down from the initial count.
+        lapic.set_timer_initial(u32::MAX);
+        let freq = calibrate(|| (u32::MAX - lapic.timer_current()) as u64).unwrap_or_else(|| {
+            // measure it against the calibrated TSC instead
+            let start = lapic.timer_current();
+            axplat::time::busy_wait(axplat::time::Duration::from_millis(CALIBRATE_MS));
+            (start - lapic.timer_current()) as u64 * 1_000 / CALIBRATE_MS
+        });
+        lapic.set_timer_initial(0);
+        axplat::console_println!("LAPIC timer frequency: {} MHz", freq / 1_000_000);
+        LAPIC_CLOCK = ClockSource::new(freq, 32);
+    }
+}
+
+/// Arms the HPET timer 0 for the earliest pending deadline of all CPUs. A
+/// deadline that has passed fires as soon as possible.
+///
+/// It should be called with [`HPET_LOCK`] held.
+#[cfg(feature = "irq")]
+fn arm_hpet_oneshot() {
+    let next_ns = HPET_DEADLINES[..crate::apic::cpu_count()]
+        .iter()
+        .map(|deadline| deadline.load(Ordering::Acquire))
+        .min()
+        .unwrap_or(NO_DEADLINE);
+    if next_ns != NO_DEADLINE {
+        let now_ns = axplat::time::monotonic_time_nanos();
+        crate::hpet::set_oneshot(next_ns.saturating_sub(now_ns));
+    }
+}
+
+/// Handles the HPET timer interrupt on the BSP.
+///
+/// The deadlines that have passed are cleared, the CPUs other than the BSP
+/// are notified by timer IPIs, and the HPET is armed for the next deadline.
+/// Returns whether the deadline of the BSP has passed.
+#[cfg(feature = "irq")]
+fn forward_hpet_oneshot() -> bool {
+    let _guard = HPET_LOCK.lock();
+    let now_ns = axplat::time::monotonic_time_nanos();
+    let mut expired = false;
+    for (cpu_id, deadline) in HPET_DEADLINES[..crate::apic::cpu_count()]
+        .iter()
+        .enumerate()
+    {
+        if deadline.load(Ordering::Acquire) > now_ns {
+            continue;
+        }
+        deadline.store(NO_DEADLINE, Ordering::Release);
+        // The BSP is always logical CPU 0.
+        if cpu_id == 0 {
+            expired = true;
+        } else {
+            crate::apic::send_ipi_to(cpu_id, crate::apic::vectors::APIC_TIMER_VECTOR);
+        }
+    }
+    arm_hpet_oneshot();
+    expired
+}
+
+/// Re-arms the one-shot timer if it fires before the pending deadline of the
+/// current CPU.
+///
+/// If the HPET is used for one-shot timers, its interrupts on the BSP are
+/// forwarded to other CPUs whose deadlines have passed, and the timer IPIs
+/// received by other CPUs are always handled.
+///
+/// Returns `true` if the timer is re-armed, and the interrupt should not be
+/// handled further.
+#[cfg(feature = "irq")]
+pub fn rearm_oneshot_timer() -> bool {
+    if unsafe { HPET_ONESHOT } {
+        let is_bsp = unsafe { super::apic::local_apic().is_bsp() };
+        return is_bsp && !forward_hpet_oneshot();
+    }
+    if unsafe { TSC_DEADLINE } {
+        return false;
+    }
+    let deadline_ns = ONESHOT_DEADLINE_NS.read_current();
+    let now_ns = axplat::time::monotonic_time_nanos();
+    if now_ns < deadline_ns {
+        set_lapic_countdown(deadline_ns - now_ns);
+        true
+    } else {
+        false
+    }
+}
+
+/// Arms the LAPIC count-down timer to fire after the given nanoseconds.
+///
+/// The timer fires early if the interval is longer than the 32-bit count, and
+/// is re-armed by [`rearm_oneshot_timer`].
+#[cfg(feature = "irq")]
+fn set_lapic_countdown(delta_ns: u64) {
+    let apic_ticks = unsafe { LAPIC_CLOCK }
+        .nanos_to_ticks(delta_ns)
+        .clamp(1, u32::MAX as u64);
+    unsafe { super::apic::local_apic().set_timer_initial(apic_ticks as u32) };
+}
+
 pub fn init_primary() {
     #[cfg(feature = "irq")]
     unsafe {
-        use x2apic::lapic::{TimerDivide, TimerMode};
-        let lapic = super::apic::local_apic();
-        lapic.set_timer_mode(TimerMode::OneShot);
-        lapic.set_timer_divide(TimerDivide::Div256); // indeed it is Div1, the name is confusing.
-        lapic.enable_timer();
+        let has_tsc_deadline = CpuId::new()
+            .get_feature_info()
+            .is_some_and(|info| info.has_tsc_deadline());
+        // The deadline is computed from the clock source.
+        TSC_DEADLINE = has_tsc_deadline && CLOCK_IS_TSC;
+        init_lapic_timer();
+        if TSC_DEADLINE {
+            info!("Using LAPIC timer in TSC-deadline mode.");
+        } else {
+            calibrate_lapic_timer();
+        }
 
-        // TODO: calibrate with HPET
-        NANOS_TO_LAPIC_TICKS_RATIO = Ratio::new(
-            LAPIC_TICKS_PER_SEC as u32,
-            axplat::time::NANOS_PER_SEC as u32,
-        );
+        let has_arat = CpuId::new()
+            .get_thermal_power_info()
+            .is_some_and(|info| info.has_arat());
+        if !has_arat
+            && crate::hpet::is_present()
+            && crate::hpet::init_oneshot(crate::apic::vectors::APIC_TIMER_VECTOR)
+        {
+            info!("LAPIC timer may stop in C-states, using HPET for one-shot timers.");
+            HPET_ONESHOT = true;
+        }
     }
 }
 
@@ -69,7 +343,7 @@ pub fn init_primary() {
 pub fn init_secondary() {
     #[cfg(feature = "irq")]
     unsafe {
-        crate::apic::local_apic().enable_timer();
+        init_lapic_timer();
     }
 }
 
@@ -79,17 +353,17 @@ struct TimeIfImpl;
 impl TimeIf for TimeIfImpl {
     /// Returns the current clock time in hardware ticks.
     fn current_ticks() -> u64 {
-        unsafe { core::arch::x86_64::_rdtsc() - INIT_TICK }
+        unsafe { READ_COUNTER() - INIT_TICK }
     }
 
     /// Converts hardware ticks to nanoseconds.
     fn ticks_to_nanos(ticks: u64) -> u64 {
-        ticks * 1_000 / unsafe { CPU_FREQ_MHZ }
+        unsafe { CLOCK.ticks_to_nanos(ticks) }
     }
 
     /// Converts nanoseconds to hardware ticks.
     fn nanos_to_ticks(nanos: u64) -> u64 {
-        nanos * unsafe { CPU_FREQ_MHZ } / 1_000
+        unsafe { CLOCK.nanos_to_ticks(nanos) }
     }
 
     /// Return epoch offset in nanoseconds (wall time offset to monotonic
@@ -104,17 +378,20 @@ impl TimeIf for TimeIfImpl {
     /// deadline (in nanoseconds).
     fn set_oneshot_timer(deadline_ns: u64) {
         #[cfg(feature = "irq")]
-        {
-            let lapic = super::apic::local_apic();
+        unsafe {
             let now_ns = Self::ticks_to_nanos(Self::current_ticks());
-            unsafe {
-                if now_ns < deadline_ns {
-                    let apic_ticks = NANOS_TO_LAPIC_TICKS_RATIO.mul_trunc(deadline_ns - now_ns);
-                    assert!(apic_ticks <= u32::MAX as u64);
-                    lapic.set_timer_initial(apic_ticks.max(1) as u32);
-                } else {
-                    lapic.set_timer_initial(1);
-                }
+            if HPET_ONESHOT {
+                let cpu_id = super::apic::current_cpu_id().unwrap_or(0);
+                let _guard = HPET_LOCK.lock();
+                HPET_DEADLINES[cpu_id].store(deadline_ns, Ordering::Release);
+                arm_hpet_oneshot();
+            } else if TSC_DEADLINE {
+                // A deadline in the past fires immediately.
+                let tsc_deadline = INIT_TICK + CLOCK.nanos_to_ticks(deadline_ns);
+                x86::msr::wrmsr(x86::msr::IA32_TSC_DEADLINE, tsc_deadline.max(1));
+            } else {
+                ONESHOT_DEADLINE_NS.write_current(deadline_ns);
+                set_lapic_countdown(deadline_ns.saturating_sub(now_ns));
             }
         }
         #[cfg(not(feature = "irq"))]